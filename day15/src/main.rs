@@ -1,14 +1,24 @@
-use std::{collections::VecDeque, fmt::Display};
+use std::{
+    cmp::Reverse,
+    collections::VecDeque,
+    fmt::Display,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{self, BufRead, Write},
+    time::Instant,
+};
 
 use glam::IVec2;
 
-#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Hash)]
 enum Tile {
     Wall,
     Robot,
-    Box,
-    BoxL,
-    BoxR,
+    Box {
+        row: u8,
+        col: u8,
+        height: u8,
+        width: u8,
+    },
     Empty,
 }
 use Tile::*;
@@ -18,15 +28,16 @@ impl Display for Tile {
         match self {
             Wall => write!(f, "#"),
             Robot => write!(f, "@"),
-            Box => write!(f, "O"),
-            BoxL => write!(f, "["),
-            BoxR => write!(f, "]"),
+            Box { width: 1, .. } => write!(f, "O"),
+            Box { col: 0, .. } => write!(f, "["),
+            Box { col, width, .. } if col + 1 == *width => write!(f, "]"),
+            Box { .. } => write!(f, "="),
             Empty => write!(f, "."),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Direction {
     Up,
     Down,
@@ -67,23 +78,35 @@ impl Display for State {
 }
 
 impl State {
-    fn widen(&mut self) {
+    fn widen(&mut self, horizontal: u8, vertical: u8) {
         self.map = self
             .map
             .iter()
-            .map(|l| {
-                l.iter()
-                    .flat_map(|t| match t {
-                        Empty => [Empty, Empty],
-                        Wall => [Wall, Wall],
-                        Box => [BoxL, BoxR],
-                        Robot => [Robot, Empty],
-                        e => [*e, *e],
-                    })
-                    .collect()
+            .flat_map(|l| {
+                (0..vertical).map(move |r| {
+                    l.iter()
+                        .flat_map(|t| {
+                            (0..horizontal).map(move |c| match t {
+                                Box {
+                                    row,
+                                    col,
+                                    height,
+                                    width,
+                                } => Box {
+                                    row: row * vertical + r,
+                                    col: col * horizontal + c,
+                                    height: height * vertical,
+                                    width: width * horizontal,
+                                },
+                                Robot if r != 0 || c != 0 => Empty,
+                                e => *e,
+                            })
+                        })
+                        .collect()
+                })
             })
             .collect();
-        self.robot_pos *= IVec2::new(1, 2);
+        self.robot_pos *= IVec2::new(vertical as i32, horizontal as i32);
     }
 }
 
@@ -104,7 +127,12 @@ fn parse(input: &str) -> State {
                             robot_pos.y = c_idx as i32;
                             Robot
                         }
-                        'O' => Box,
+                        'O' => Box {
+                            row: 0,
+                            col: 0,
+                            height: 1,
+                            width: 1,
+                        },
                         _ => Empty,
                     })
                     .collect()
@@ -130,6 +158,22 @@ fn get_tile<'a>(map: &'a mut [Vec<Tile>], idx: &IVec2) -> &'a mut Tile {
     &mut map[idx.x as usize][idx.y as usize]
 }
 
+fn box_cells(pos: &IVec2, tile: &Tile) -> Vec<IVec2> {
+    let Box {
+        row,
+        col,
+        height,
+        width,
+    } = *tile
+    else {
+        return vec![];
+    };
+    let top_left = pos - IVec2::new(row as i32, col as i32);
+    (0..height as i32)
+        .flat_map(|r| (0..width as i32).map(move |c| top_left + IVec2::new(r, c)))
+        .collect()
+}
+
 fn can_be_moved(state: &State, pos: &IVec2, direction: &IVec2, to_move: &mut Vec<IVec2>) -> bool {
     let tile_in_front = state.map[(pos + direction).x as usize][(pos + direction).y as usize];
     if tile_in_front == Wall {
@@ -139,24 +183,55 @@ fn can_be_moved(state: &State, pos: &IVec2, direction: &IVec2, to_move: &mut Vec
         return true;
     }
 
-    let to_check_dir = match tile_in_front {
-        BoxL => Right.as_vec(),
-        BoxR => Left.as_vec(),
-        _ => IVec2::new(0, 0),
-    };
+    let cells = box_cells(&(pos + direction), &tile_in_front);
+    to_move.extend(&cells);
+    cells
+        .iter()
+        .filter(|c| !cells.contains(&(*c + direction)))
+        .all(|c| can_be_moved(state, c, direction, to_move))
+}
+
+fn do_move(state: &mut State) {
+    let direction = state.directions.pop_front().unwrap();
+    apply_move(state, &direction);
+}
 
-    if direction == &Up.as_vec() || direction == &Down.as_vec() {
-        to_move.push(pos + direction);
-        to_move.push(pos + direction + to_check_dir);
-        return can_be_moved(state, &(pos + direction), direction, to_move)
-            && can_be_moved(state, &(pos + direction + to_check_dir), direction, to_move);
+fn collect_to_move(map: &[Vec<Tile>], robot_pos: &IVec2, dir: &IVec2) -> Option<Vec<IVec2>> {
+    let mut to_move = vec![*robot_pos];
+    let mut idx = 0;
+    while idx < to_move.len() {
+        let pos_in_front = to_move[idx] + dir;
+        idx += 1;
+        let tile_in_front = map[pos_in_front.x as usize][pos_in_front.y as usize];
+        match tile_in_front {
+            Wall => return None,
+            Empty => continue,
+            _ => (),
+        }
+        for pos in box_cells(&pos_in_front, &tile_in_front) {
+            if !to_move.contains(&pos) {
+                to_move.push(pos);
+            }
+        }
     }
-    to_move.push(pos + direction);
-    can_be_moved(state, &(pos + direction), direction, to_move)
+    to_move.sort_unstable_by_key(|p| Reverse(p.dot(*dir)));
+    Some(to_move)
 }
 
-fn do_move(state: &mut State) {
-    let dir = state.directions.pop_front().unwrap().as_vec();
+fn apply_move(state: &mut State, direction: &Direction) {
+    let dir = direction.as_vec();
+    let Some(to_move) = collect_to_move(&state.map, &state.robot_pos, &dir) else {
+        return;
+    };
+    for pos in &to_move {
+        *get_tile(&mut state.map, &(pos + dir)) = *get_tile(&mut state.map, pos);
+        *get_tile(&mut state.map, pos) = Empty;
+    }
+    state.robot_pos += dir;
+}
+
+fn apply_move_cloning(state: &mut State, direction: &Direction) {
+    let dir = direction.as_vec();
     let pos_in_front = state.robot_pos + dir;
 
     if get_tile(&mut state.map, &pos_in_front) == &Wall {
@@ -194,7 +269,7 @@ fn get_gps_sum(state: &State) -> usize {
         .enumerate()
         .flat_map(|(l_idx, l)| {
             l.iter().enumerate().map(move |(t_idx, t)| {
-                if t == &Box || t == &BoxL {
+                if matches!(t, Box { row: 0, col: 0, .. }) {
                     100 * l_idx + t_idx
                 } else {
                     0
@@ -204,6 +279,104 @@ fn get_gps_sum(state: &State) -> usize {
         .sum()
 }
 
+type MoveFn = fn(&mut State, &Direction);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LogEntry {
+    direction: Direction,
+    hash: u64,
+    changed: Vec<IVec2>,
+}
+
+impl Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} {:016x}", self.direction, self.hash)?;
+        for pos in &self.changed {
+            write!(f, " {},{}", pos.x, pos.y)?;
+        }
+        Ok(())
+    }
+}
+
+fn state_hash(state: &State) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    state.map.hash(&mut hasher);
+    state.robot_pos.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn logged_move(state: &mut State, direction: &Direction, move_fn: MoveFn) -> LogEntry {
+    let before = state.map.clone();
+    move_fn(state, direction);
+    let changed = before
+        .iter()
+        .zip(&state.map)
+        .enumerate()
+        .flat_map(|(l_idx, (l1, l2))| {
+            l1.iter()
+                .zip(l2)
+                .enumerate()
+                .filter(|(_, (t1, t2))| t1 != t2)
+                .map(move |(t_idx, _)| IVec2::new(l_idx as i32, t_idx as i32))
+        })
+        .collect();
+    LogEntry {
+        direction: *direction,
+        hash: state_hash(state),
+        changed,
+    }
+}
+
+fn record_moves(state: &mut State, move_fn: MoveFn) -> Vec<LogEntry> {
+    let mut log = vec![];
+    while let Some(direction) = state.directions.pop_front() {
+        log.push(logged_move(state, &direction, move_fn));
+    }
+    log
+}
+
+fn find_divergence(state: &State, move_fn1: MoveFn, move_fn2: MoveFn) -> Option<usize> {
+    let mut state1 = state.clone();
+    let mut state2 = state.clone();
+    for (idx, direction) in state.directions.iter().enumerate() {
+        let entry1 = logged_move(&mut state1, direction, move_fn1);
+        let entry2 = logged_move(&mut state2, direction, move_fn2);
+        if entry1 != entry2 {
+            println!("first divergence at move {idx} ({direction:?})");
+            println!("{entry1}\n{state1}");
+            println!("{entry2}\n{state2}");
+            return Some(idx);
+        }
+    }
+    None
+}
+
+fn benchmark(state: &State, rounds: usize) {
+    let moves = rounds * state.directions.len();
+    let mut results = vec![];
+    for (name, move_fn) in [
+        ("cloning", apply_move_cloning as MoveFn),
+        ("in-place", apply_move),
+    ] {
+        let start = Instant::now();
+        let mut gps_sum = 0;
+        for _ in 0..rounds {
+            let mut state = state.clone();
+            while let Some(direction) = state.directions.pop_front() {
+                move_fn(&mut state, &direction);
+            }
+            gps_sum = get_gps_sum(&state);
+        }
+        let elapsed = start.elapsed();
+        println!(
+            "{name:>8}: {gps_sum} in {elapsed:?} ({:.0} moves/s)",
+            moves as f64 / elapsed.as_secs_f64()
+        );
+        results.push(gps_sum);
+    }
+    assert_eq!(results[0], results[1]);
+}
+
 fn part12(state: &mut State) -> usize {
     while !state.directions.is_empty() {
         do_move(state);
@@ -211,9 +384,120 @@ fn part12(state: &mut State) -> usize {
     get_gps_sum(state)
 }
 
+struct Snapshot {
+    map: Vec<Vec<Tile>>,
+    robot_pos: IVec2,
+    scripted: Option<Direction>,
+}
+
+fn step_through(state: &mut State) {
+    let mut history: Vec<Snapshot> = vec![];
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        print!("\x1b[2J\x1b[H{}", state);
+        println!(
+            "move {} | {} scripted left | gps {}",
+            history.len(),
+            state.directions.len(),
+            get_gps_sum(state)
+        );
+        print!("[enter] next scripted, <^>v manual, u undo, q quit: ");
+        io::stdout().flush().unwrap();
+
+        let Some(Ok(line)) = lines.next() else {
+            return;
+        };
+        let commands: Vec<char> = if line.trim().is_empty() {
+            vec!['\n']
+        } else {
+            line.trim().chars().collect()
+        };
+        for command in commands {
+            let direction = match command {
+                '\n' => match state.directions.front() {
+                    Some(d) => *d,
+                    None => continue,
+                },
+                '<' => Left,
+                '^' => Up,
+                '>' => Right,
+                'v' => Down,
+                'u' => {
+                    if let Some(snapshot) = history.pop() {
+                        state.map = snapshot.map;
+                        state.robot_pos = snapshot.robot_pos;
+                        if let Some(d) = snapshot.scripted {
+                            state.directions.push_front(d);
+                        }
+                    }
+                    continue;
+                }
+                'q' => return,
+                _ => continue,
+            };
+            let scripted = (command == '\n').then(|| state.directions.pop_front().unwrap());
+            history.push(Snapshot {
+                map: state.map.clone(),
+                robot_pos: state.robot_pos,
+                scripted,
+            });
+            apply_move(state, &direction);
+        }
+    }
+}
+
+fn parse_scale(args: &[String]) -> Result<(u8, u8), String> {
+    let Some(i) = args.iter().position(|a| a == "--scale") else {
+        return Ok((2, 1));
+    };
+    let factor = |value: Option<&String>| {
+        value
+            .and_then(|v| v.parse::<u8>().ok())
+            .filter(|f| *f > 0)
+            .ok_or("--scale takes a horizontal and a vertical factor between 1 and 255")
+    };
+    Ok((factor(args.get(i + 1))?, factor(args.get(i + 2))?))
+}
+
 fn main() {
     let mut input = parse(include_str!("../input.txt"));
+    let args: Vec<String> = std::env::args().collect();
+    let (horizontal, vertical) = parse_scale(&args).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    if args.iter().any(|a| a == "--step") {
+        if args.iter().any(|a| a == "--wide") {
+            input.widen(horizontal, vertical);
+        }
+        step_through(&mut input);
+        return;
+    }
+    if args.iter().any(|a| a == "--log") {
+        if args.iter().any(|a| a == "--wide") {
+            input.widen(horizontal, vertical);
+        }
+        for entry in record_moves(&mut input, apply_move) {
+            println!("{entry}");
+        }
+        return;
+    }
+    if args.iter().any(|a| a == "--diff") {
+        let narrow = find_divergence(&input, apply_move_cloning, apply_move);
+        input.widen(horizontal, vertical);
+        let wide = find_divergence(&input, apply_move_cloning, apply_move);
+        if narrow.is_none() && wide.is_none() {
+            println!("no divergence");
+        }
+        return;
+    }
+    if args.iter().any(|a| a == "--bench") {
+        benchmark(&input, 100);
+        input.widen(horizontal, vertical);
+        benchmark(&input, 100);
+        return;
+    }
     println!("{}", part12(&mut input.clone()));
-    input.widen();
+    input.widen(horizontal, vertical);
     println!("{}", part12(&mut input));
 }
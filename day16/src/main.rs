@@ -1,4 +1,4 @@
-use std::{cmp::Reverse, collections::BinaryHeap};
+use std::{cmp::Reverse, collections::BinaryHeap, fmt::Display, iter, str::FromStr};
 
 use ahash::{AHashMap, AHashSet};
 use glam::IVec2;
@@ -57,6 +57,79 @@ impl Direction {
             East => West,
         }
     }
+
+    fn all() -> [Self; 4] {
+        [North, South, West, East]
+    }
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            North => write!(f, "^"),
+            South => write!(f, "v"),
+            West => write!(f, "<"),
+            East => write!(f, ">"),
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "N" | "north" => Ok(North),
+            "S" | "south" => Ok(South),
+            "W" | "west" => Ok(West),
+            "E" | "east" => Ok(East),
+            _ => Err(format!("invalid direction: {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rules {
+    step_cost: u32,
+    turn_cost: u32,
+    u_turn_cost: Option<u32>,
+    start_direction: Direction,
+    end_direction: Option<Direction>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            step_cost: 1,
+            turn_cost: 1000,
+            u_turn_cost: None,
+            start_direction: East,
+            end_direction: None,
+        }
+    }
+}
+
+impl Rules {
+    fn turns(&self, direction: &Direction) -> impl Iterator<Item = (Direction, u32)> {
+        [
+            (direction.turn_left(), self.turn_cost),
+            (direction.turn_right(), self.turn_cost),
+        ]
+        .into_iter()
+        .chain(self.u_turn_cost.map(|c| (direction.turn_around(), c)))
+    }
+
+    fn is_end(&self, direction: &Direction) -> bool {
+        self.end_direction.is_none_or(|d| d == *direction)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        // zero costs leave ties that the searches and route counts can't order
+        if self.step_cost == 0 || self.turn_cost == 0 || self.u_turn_cost == Some(0) {
+            return Err("step, turn and u-turn costs must be positive".into());
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
@@ -110,14 +183,18 @@ fn parse(input: &str) -> (Vec<Vec<Tile>>, IVec2, IVec2) {
     )
 }
 
-fn get_scores(grid: &[Vec<Tile>], start_pos: &IVec2) -> AHashMap<(IVec2, Direction), u32> {
+fn get_scores(
+    grid: &[Vec<Tile>],
+    start_pos: &IVec2,
+    rules: &Rules,
+) -> AHashMap<(IVec2, Direction), u32> {
     // dijkstra
 
     let mut to_visit = BinaryHeap::new();
     let mut visited = AHashMap::new();
     to_visit.push(Reverse(Node {
         score: 0,
-        direction: East,
+        direction: rules.start_direction,
         position: *start_pos,
     }));
 
@@ -126,7 +203,7 @@ fn get_scores(grid: &[Vec<Tile>], start_pos: &IVec2) -> AHashMap<(IVec2, Directi
 
         if visited
             .get(&(curr.position, curr.direction))
-            .map_or(false, |s| s < &curr.score)
+            .is_some_and(|s| s <= &curr.score)
         {
             continue;
         }
@@ -135,31 +212,68 @@ fn get_scores(grid: &[Vec<Tile>], start_pos: &IVec2) -> AHashMap<(IVec2, Directi
         let pos_in_front = curr.position + curr.direction.as_vec();
         if grid[pos_in_front.x as usize][pos_in_front.y as usize] != Wall {
             to_visit.push(Reverse(Node {
-                score: curr.score + 1,
+                score: curr.score + rules.step_cost,
                 direction: curr.direction,
                 position: curr.position + curr.direction.as_vec(),
             }));
         }
 
-        to_visit.push(Reverse(Node {
-            score: curr.score + 1000,
-            direction: curr.direction.turn_left(),
-            position: curr.position,
-        }));
-
-        to_visit.push(Reverse(Node {
-            score: curr.score + 1000,
-            direction: curr.direction.turn_right(),
-            position: curr.position,
-        }));
+        for (direction, cost) in rules.turns(&curr.direction) {
+            to_visit.push(Reverse(Node {
+                score: curr.score + cost,
+                direction,
+                position: curr.position,
+            }));
+        }
     }
     visited
 }
 
-fn part1(scores: &AHashMap<(IVec2, Direction), u32>, end_pos: &IVec2) -> u32 {
+fn brute_force_scores(
+    grid: &[Vec<Tile>],
+    start_pos: &IVec2,
+    rules: &Rules,
+) -> AHashMap<(IVec2, Direction), u32> {
+    // relax every transition until nothing changes
+
+    let mut scores = AHashMap::from_iter([((*start_pos, rules.start_direction), 0)]);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (l_idx, l) in grid.iter().enumerate() {
+            for (c_idx, t) in l.iter().enumerate() {
+                if t == &Wall {
+                    continue;
+                }
+                let position = IVec2::new(l_idx as i32, c_idx as i32);
+                for direction in Direction::all() {
+                    let Some(&score) = scores.get(&(position, direction)) else {
+                        continue;
+                    };
+                    let pos_in_front = position + direction.as_vec();
+                    let step = (grid[pos_in_front.x as usize][pos_in_front.y as usize] != Wall)
+                        .then_some(((pos_in_front, direction), rules.step_cost));
+                    for (key, cost) in rules
+                        .turns(&direction)
+                        .map(|(d, c)| ((position, d), c))
+                        .chain(step)
+                    {
+                        if scores.get(&key).is_none_or(|s| *s > score + cost) {
+                            scores.insert(key, score + cost);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    scores
+}
+
+fn part1(scores: &AHashMap<(IVec2, Direction), u32>, end_pos: &IVec2, rules: &Rules) -> u32 {
     *scores
         .iter()
-        .filter(|((p, _), _)| p == end_pos)
+        .filter(|((p, d), _)| p == end_pos && rules.is_end(d))
         .map(|(_, v)| v)
         .min()
         .unwrap()
@@ -172,14 +286,14 @@ struct Visit {
     score: u32,
 }
 
-fn part2(scores: &AHashMap<(IVec2, Direction), u32>, end_pos: &IVec2) -> usize {
+fn part2(scores: &AHashMap<(IVec2, Direction), u32>, end_pos: &IVec2, rules: &Rules) -> usize {
     // bfs from end to start along shortest paths
 
-    let shortest_path_len = part1(scores, end_pos);
-    let mut seen: AHashSet<IVec2> = AHashSet::new();
+    let shortest_path_len = part1(scores, end_pos, rules);
+    let mut seen: AHashSet<(IVec2, Direction)> = AHashSet::new();
     let mut to_visit: Vec<Visit> = scores
         .iter()
-        .filter(|((p, _), val)| val == &&shortest_path_len && p == end_pos)
+        .filter(|((p, d), val)| val == &&shortest_path_len && p == end_pos && rules.is_end(d))
         .map(|((p, d), s)| Visit {
             position: *p,
             direction: *d,
@@ -188,48 +302,576 @@ fn part2(scores: &AHashMap<(IVec2, Direction), u32>, end_pos: &IVec2) -> usize {
         .collect();
 
     while let Some(curr) = to_visit.pop() {
-        seen.insert(curr.position);
+        if !seen.insert((curr.position, curr.direction)) {
+            continue;
+        }
 
         let pos_behind = curr.position + curr.direction.turn_around().as_vec();
-        if scores
-            .get(&(pos_behind, curr.direction))
-            .map_or(false, |v| v == &(curr.score - 1))
+        if curr.score >= rules.step_cost
+            && scores.get(&(pos_behind, curr.direction)) == Some(&(curr.score - rules.step_cost))
         {
             to_visit.push(Visit {
                 position: pos_behind,
                 direction: curr.direction,
-                score: curr.score - 1,
+                score: curr.score - rules.step_cost,
             });
         }
 
-        let new_score = curr.score - 1000;
-        if scores
-            .get(&(curr.position, curr.direction.turn_left()))
-            .map_or(false, |v| v == &new_score)
-        {
-            to_visit.push(Visit {
-                position: curr.position,
-                direction: curr.direction.turn_left(),
-                score: new_score,
-            });
+        // the turns are symmetric, so the directions we could have turned from are the same
+        for (direction, cost) in rules.turns(&curr.direction) {
+            if curr.score >= cost
+                && scores.get(&(curr.position, direction)) == Some(&(curr.score - cost))
+            {
+                to_visit.push(Visit {
+                    position: curr.position,
+                    direction,
+                    score: curr.score - cost,
+                });
+            }
         }
-        if scores
-            .get(&(curr.position, curr.direction.turn_right()))
-            .map_or(false, |v| v == &new_score)
-        {
-            to_visit.push(Visit {
-                position: curr.position,
-                direction: curr.direction.turn_right(),
-                score: new_score,
-            });
+    }
+    seen.iter().map(|(p, _)| p).collect::<AHashSet<_>>().len()
+}
+
+struct RadixHeap<T> {
+    last: u32,
+    len: usize,
+    buckets: Vec<Vec<(u32, T)>>,
+}
+
+impl<T> RadixHeap<T> {
+    fn new() -> Self {
+        RadixHeap {
+            last: 0,
+            len: 0,
+            buckets: (0..=32).map(|_| vec![]).collect(),
+        }
+    }
+
+    fn bucket(&self, key: u32) -> usize {
+        32 - (key ^ self.last).leading_zeros() as usize
+    }
+
+    fn push(&mut self, key: u32, value: T) {
+        debug_assert!(key >= self.last);
+        let bucket = self.bucket(key);
+        self.buckets[bucket].push((key, value));
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<(u32, T)> {
+        if self.len == 0 {
+            return None;
+        }
+        if self.buckets[0].is_empty() {
+            let idx = self.buckets.iter().position(|b| !b.is_empty()).unwrap();
+            let entries = std::mem::take(&mut self.buckets[idx]);
+            self.last = entries.iter().map(|(k, _)| *k).min().unwrap();
+            for (key, value) in entries {
+                let bucket = self.bucket(key);
+                self.buckets[bucket].push((key, value));
+            }
         }
+        self.len -= 1;
+        self.buckets[0].pop()
     }
-    seen.len()
+}
+
+#[derive(Debug, Clone)]
+struct Edge {
+    from: (IVec2, Direction),
+    to: (IVec2, Direction),
+    cost: u32,
+    cells: Vec<IVec2>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct JunctionGraph {
+    edges: Vec<Edge>,
+    outgoing: AHashMap<(IVec2, Direction), usize>,
+    incoming: AHashMap<(IVec2, Direction), Vec<usize>>,
+}
+
+fn build_junction_graph(
+    grid: &[Vec<Tile>],
+    start_pos: &IVec2,
+    end_pos: &IVec2,
+    rules: &Rules,
+) -> JunctionGraph {
+    let mut open: Vec<Vec<bool>> = grid
+        .iter()
+        .map(|l| l.iter().map(|t| t != &Wall).collect())
+        .collect();
+    let is_open = |open: &[Vec<bool>], pos: IVec2| open[pos.x as usize][pos.y as usize];
+    let degree = |open: &[Vec<bool>], pos: IVec2| {
+        Direction::all()
+            .iter()
+            .filter(|d| is_open(open, pos + d.as_vec()))
+            .count()
+    };
+
+    // fill dead ends that contain neither start nor end
+    let mut to_fill: Vec<IVec2> = (0..grid.len() as i32)
+        .flat_map(|x| (0..grid[0].len() as i32).map(move |y| IVec2::new(x, y)))
+        .filter(|p| is_open(&open, *p) && degree(&open, *p) <= 1)
+        .collect();
+    while let Some(pos) = to_fill.pop() {
+        if pos == *start_pos || pos == *end_pos || !is_open(&open, pos) {
+            continue;
+        }
+        open[pos.x as usize][pos.y as usize] = false;
+        for direction in Direction::all() {
+            let neighbor = pos + direction.as_vec();
+            if is_open(&open, neighbor) && degree(&open, neighbor) <= 1 {
+                to_fill.push(neighbor);
+            }
+        }
+    }
+
+    let is_junction = |pos: IVec2| pos == *start_pos || pos == *end_pos || degree(&open, pos) != 2;
+
+    let mut graph = JunctionGraph::default();
+    for (l_idx, l) in open.iter().enumerate() {
+        for (c_idx, _) in l.iter().enumerate().filter(|(_, o)| **o) {
+            let junction = IVec2::new(l_idx as i32, c_idx as i32);
+            if !is_junction(junction) {
+                continue;
+            }
+            for start_direction in Direction::all() {
+                let mut position = junction + start_direction.as_vec();
+                if !is_open(&open, position) {
+                    continue;
+                }
+                let mut direction = start_direction;
+                let mut cost = rules.step_cost;
+                let mut cells = vec![];
+                while !is_junction(position) {
+                    cells.push(position);
+                    if !is_open(&open, position + direction.as_vec()) {
+                        direction = [direction.turn_left(), direction.turn_right()]
+                            .into_iter()
+                            .find(|d| is_open(&open, position + d.as_vec()))
+                            .unwrap();
+                        cost += rules.turn_cost;
+                    }
+                    position += direction.as_vec();
+                    cost += rules.step_cost;
+                }
+                let edge = Edge {
+                    from: (junction, start_direction),
+                    to: (position, direction),
+                    cost,
+                    cells,
+                };
+                graph.outgoing.insert(edge.from, graph.edges.len());
+                graph
+                    .incoming
+                    .entry(edge.to)
+                    .or_default()
+                    .push(graph.edges.len());
+                graph.edges.push(edge);
+            }
+        }
+    }
+    graph
+}
+
+type Scores = AHashMap<(IVec2, Direction), u32>;
+type RouteCounts = AHashMap<(IVec2, Direction), u128>;
+
+fn get_junction_scores(
+    graph: &JunctionGraph,
+    start_pos: &IVec2,
+    end_pos: &IVec2,
+    rules: &Rules,
+) -> (Scores, RouteCounts) {
+    // dijkstra on the junction graph, stopping once no end state can improve
+
+    let mut to_visit = RadixHeap::new();
+    let mut tentative: AHashMap<(IVec2, Direction), (u32, u128)> = AHashMap::new();
+    let mut visited = AHashMap::new();
+    let mut counts = AHashMap::new();
+    let mut best_end = u32::MAX;
+    to_visit.push(0, (*start_pos, rules.start_direction));
+    tentative.insert((*start_pos, rules.start_direction), (0, 1));
+
+    while let Some((score, state)) = to_visit.pop() {
+        if score > best_end {
+            break;
+        }
+        if visited.contains_key(&state) {
+            continue;
+        }
+        let count = tentative[&state].1;
+        visited.insert(state, score);
+        counts.insert(state, count);
+        if state.0 == *end_pos && rules.is_end(&state.1) {
+            best_end = score;
+        }
+
+        let step = graph
+            .outgoing
+            .get(&state)
+            .map(|edge| (graph.edges[*edge].to, graph.edges[*edge].cost));
+        let turns = rules.turns(&state.1).map(|(d, cost)| ((state.0, d), cost));
+        for (next, cost) in step.into_iter().chain(turns) {
+            if visited.contains_key(&next) {
+                continue;
+            }
+            let new_score = score + cost;
+            match tentative.get_mut(&next) {
+                Some((s, c)) if *s == new_score => *c = c.saturating_add(count),
+                Some((s, _)) if *s < new_score => (),
+                _ => {
+                    tentative.insert(next, (new_score, count));
+                    to_visit.push(new_score, next);
+                }
+            }
+        }
+    }
+    (visited, counts)
+}
+
+fn count_best_routes(
+    scores: &Scores,
+    counts: &RouteCounts,
+    end_pos: &IVec2,
+    rules: &Rules,
+) -> u128 {
+    let shortest_path_len = part1(scores, end_pos, rules);
+    scores
+        .iter()
+        .filter(|((p, d), val)| val == &&shortest_path_len && p == end_pos && rules.is_end(d))
+        .map(|(k, _)| counts[k])
+        .fold(0, u128::saturating_add)
+}
+
+fn k_shortest_routes(
+    graph: &JunctionGraph,
+    start_pos: &IVec2,
+    end_pos: &IVec2,
+    rules: &Rules,
+    k: usize,
+) -> Vec<(u32, Vec<(IVec2, Direction)>)> {
+    // best first search over partial routes, guided by the exact remaining cost.
+    // a route may not pass through the same (position, direction) state twice
+
+    let mut remaining: Scores = AHashMap::new();
+    let mut to_visit = RadixHeap::new();
+    for direction in Direction::all().into_iter().filter(|d| rules.is_end(d)) {
+        to_visit.push(0, (*end_pos, direction));
+    }
+    while let Some((score, state)) = to_visit.pop() {
+        if remaining.contains_key(&state) {
+            continue;
+        }
+        remaining.insert(state, score);
+        let steps = graph
+            .incoming
+            .get(&state)
+            .into_iter()
+            .flatten()
+            .map(|e| (graph.edges[*e].from, graph.edges[*e].cost));
+        let turns = rules.turns(&state.1).map(|(d, cost)| ((state.0, d), cost));
+        for (prev, cost) in steps.chain(turns) {
+            if !remaining.contains_key(&prev) {
+                to_visit.push(score + cost, prev);
+            }
+        }
+    }
+
+    // partial routes are stored as (state, score, parent index)
+    let mut routes = vec![];
+    let mut partial: Vec<((IVec2, Direction), u32, usize)> = vec![];
+    let mut to_visit = RadixHeap::new();
+    let start = (*start_pos, rules.start_direction);
+    if let Some(h) = remaining.get(&start) {
+        partial.push((start, 0, usize::MAX));
+        to_visit.push(*h, 0);
+    }
+    while let Some((_, idx)) = to_visit.pop() {
+        let (state, score, _) = partial[idx];
+        let mut route = vec![];
+        let mut curr = idx;
+        while curr != usize::MAX {
+            route.push(partial[curr].0);
+            curr = partial[curr].2;
+        }
+        if state.0 == *end_pos && rules.is_end(&state.1) {
+            route.reverse();
+            routes.push((score, route));
+            if routes.len() == k {
+                break;
+            }
+            continue;
+        }
+
+        let step = graph
+            .outgoing
+            .get(&state)
+            .map(|edge| (graph.edges[*edge].to, graph.edges[*edge].cost));
+        let turns = rules.turns(&state.1).map(|(d, cost)| ((state.0, d), cost));
+        for (next, cost) in step.into_iter().chain(turns) {
+            let Some(h) = remaining.get(&next) else {
+                continue;
+            };
+            if route.contains(&next) {
+                continue;
+            }
+            partial.push((next, score + cost, idx));
+            to_visit.push(score + cost + h, partial.len() - 1);
+        }
+    }
+    routes
+}
+
+fn best_path_dag(
+    graph: &JunctionGraph,
+    scores: &AHashMap<(IVec2, Direction), u32>,
+    end_pos: &IVec2,
+    rules: &Rules,
+) -> (AHashSet<(IVec2, Direction)>, AHashSet<usize>) {
+    // walk back from the end along junction edges and turns that are tight
+
+    let shortest_path_len = part1(scores, end_pos, rules);
+    let mut seen_states = AHashSet::new();
+    let mut tight_edges = AHashSet::new();
+    let mut to_visit: Vec<(IVec2, Direction)> = scores
+        .iter()
+        .filter(|((p, d), val)| val == &&shortest_path_len && p == end_pos && rules.is_end(d))
+        .map(|(k, _)| *k)
+        .collect();
+
+    while let Some(curr) = to_visit.pop() {
+        if !seen_states.insert(curr) {
+            continue;
+        }
+        let score = scores[&curr];
+
+        for edge_idx in graph.incoming.get(&curr).into_iter().flatten() {
+            let edge = &graph.edges[*edge_idx];
+            if scores.get(&edge.from) == Some(&(score.wrapping_sub(edge.cost))) {
+                tight_edges.insert(*edge_idx);
+                to_visit.push(edge.from);
+            }
+        }
+        for (direction, cost) in rules.turns(&curr.1) {
+            if scores.get(&(curr.0, direction)) == Some(&(score.wrapping_sub(cost))) {
+                to_visit.push((curr.0, direction));
+            }
+        }
+    }
+    (seen_states, tight_edges)
+}
+
+fn part2_junctions(
+    graph: &JunctionGraph,
+    scores: &AHashMap<(IVec2, Direction), u32>,
+    end_pos: &IVec2,
+    rules: &Rules,
+) -> usize {
+    let (states, tight_edges) = best_path_dag(graph, scores, end_pos, rules);
+    states
+        .iter()
+        .map(|(p, _)| p)
+        .chain(tight_edges.iter().flat_map(|e| &graph.edges[*e].cells))
+        .collect::<AHashSet<_>>()
+        .len()
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct BestTile {
+    headings: u8,
+    multiplicity: u128,
+}
+
+impl BestTile {
+    fn add_heading(&mut self, direction: &Direction) {
+        self.headings |= 1 << *direction as u8;
+    }
+}
+
+fn best_tiles(
+    graph: &JunctionGraph,
+    scores: &AHashMap<(IVec2, Direction), u32>,
+    start_pos: &IVec2,
+    end_pos: &IVec2,
+    rules: &Rules,
+) -> AHashMap<IVec2, BestTile> {
+    let (states, tight_edges) = best_path_dag(graph, scores, end_pos, rules);
+    let shortest_path_len = part1(scores, end_pos, rules);
+    let mut ordered: Vec<_> = states.iter().copied().collect();
+    ordered.sort_unstable_by_key(|s| scores[s]);
+
+    let is_tight_turn = |from: &(IVec2, Direction), to: &(IVec2, Direction), cost: u32| {
+        states.contains(from) && states.contains(to) && scores[from] + cost == scores[to]
+    };
+
+    // number of best paths from the start to each state
+    let mut from_start: AHashMap<(IVec2, Direction), u128> = AHashMap::new();
+    for state in &ordered {
+        let mut count = u128::from(*state == (*start_pos, rules.start_direction));
+        for (direction, cost) in rules.turns(&state.1) {
+            if is_tight_turn(&(state.0, direction), state, cost) {
+                count = count.saturating_add(from_start[&(state.0, direction)]);
+            }
+        }
+        for edge_idx in graph.incoming.get(state).into_iter().flatten() {
+            if tight_edges.contains(edge_idx) {
+                count = count.saturating_add(from_start[&graph.edges[*edge_idx].from]);
+            }
+        }
+        from_start.insert(*state, count);
+    }
+
+    // number of best paths from each state to the end
+    let mut to_end: AHashMap<(IVec2, Direction), u128> = AHashMap::new();
+    for state in ordered.iter().rev() {
+        let mut count = u128::from(
+            state.0 == *end_pos && rules.is_end(&state.1) && scores[state] == shortest_path_len,
+        );
+        for (direction, cost) in rules.turns(&state.1) {
+            if is_tight_turn(state, &(state.0, direction), cost) {
+                count = count.saturating_add(to_end[&(state.0, direction)]);
+            }
+        }
+        if let Some(edge_idx) = graph.outgoing.get(state) {
+            if tight_edges.contains(edge_idx) {
+                count = count.saturating_add(to_end[&graph.edges[*edge_idx].to]);
+            }
+        }
+        to_end.insert(*state, count);
+    }
+
+    let mut tiles: AHashMap<IVec2, BestTile> = AHashMap::new();
+    tiles.entry(*start_pos).or_default().multiplicity =
+        to_end[&(*start_pos, rules.start_direction)];
+    for edge_idx in &tight_edges {
+        let edge = &graph.edges[*edge_idx];
+        let through = from_start[&edge.from].saturating_mul(to_end[&edge.to]);
+        tiles
+            .entry(edge.from.0)
+            .or_default()
+            .add_heading(&edge.from.1);
+        let next_cells = edge.cells.iter().skip(1).chain(iter::once(&edge.to.0));
+        for (cell, next_cell) in edge.cells.iter().zip(next_cells) {
+            let tile = tiles.entry(*cell).or_default();
+            tile.multiplicity = tile.multiplicity.saturating_add(through);
+            let heading = Direction::all()
+                .into_iter()
+                .find(|d| cell + d.as_vec() == *next_cell)
+                .unwrap();
+            tile.add_heading(&heading);
+        }
+        let tile = tiles.entry(edge.to.0).or_default();
+        tile.multiplicity = tile.multiplicity.saturating_add(through);
+    }
+    tiles
+}
+
+fn render_best_paths(
+    grid: &[Vec<Tile>],
+    tiles: &AHashMap<IVec2, BestTile>,
+    heatmap: bool,
+) -> String {
+    let max_multiplicity = tiles.values().map(|t| t.multiplicity).max().unwrap_or(1);
+    let mut res = String::new();
+    for (l_idx, l) in grid.iter().enumerate() {
+        for (c_idx, t) in l.iter().enumerate() {
+            let Some(tile) = tiles.get(&IVec2::new(l_idx as i32, c_idx as i32)) else {
+                res.push_str(match t {
+                    Wall => "\x1b[2m#\x1b[0m",
+                    _ => " ",
+                });
+                continue;
+            };
+            let symbol = match (t, tile.headings) {
+                (Start, _) => 'S',
+                (End, _) => 'E',
+                (_, h) if h.count_ones() > 1 => '+',
+                (_, h) => ['^', 'v', '<', '>'][h.trailing_zeros() as usize],
+            };
+            if heatmap {
+                // 24 step grayscale ramp of the multiplicity on a log scale
+                let level = (tile.multiplicity as f64).ln_1p() / (max_multiplicity as f64).ln_1p();
+                let color = 232 + (level * 23.0).round() as u8;
+                res.push_str(&format!("\x1b[48;5;{color}m\x1b[31m{symbol}\x1b[0m"));
+            } else {
+                res.push_str(&format!("\x1b[32m{symbol}\x1b[0m"));
+            }
+        }
+        res.push('\n');
+    }
+    res
+}
+
+fn arg<T: FromStr>(args: &[String], name: &str) -> Option<T> {
+    args.iter().position(|a| a == name).map(|i| {
+        args[i + 1]
+            .parse()
+            .unwrap_or_else(|_| panic!("invalid value for {name}"))
+    })
 }
 
 fn main() {
     let (grid, start_pos, end_pos) = parse(include_str!("../input.txt"));
-    let scores = get_scores(&grid, &start_pos);
-    println!("{}", part1(&scores, &end_pos));
-    println!("{}", part2(&scores, &end_pos));
+    let args: Vec<String> = std::env::args().collect();
+    let default = Rules::default();
+    let rules = Rules {
+        step_cost: arg(&args, "--step-cost").unwrap_or(default.step_cost),
+        turn_cost: arg(&args, "--turn-cost").unwrap_or(default.turn_cost),
+        u_turn_cost: arg(&args, "--u-turn-cost"),
+        start_direction: arg(&args, "--start").unwrap_or(default.start_direction),
+        end_direction: arg(&args, "--end"),
+    };
+    if let Err(e) = rules.validate() {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+    let graph = build_junction_graph(&grid, &start_pos, &end_pos, &rules);
+    let (scores, counts) = get_junction_scores(&graph, &start_pos, &end_pos, &rules);
+    if args.iter().any(|a| a == "--check") {
+        let full_scores = get_scores(&grid, &start_pos, &rules);
+        let brute_force = brute_force_scores(&grid, &start_pos, &rules);
+        assert_eq!(
+            part1(&scores, &end_pos, &rules),
+            part1(&full_scores, &end_pos, &rules)
+        );
+        assert_eq!(
+            part1(&scores, &end_pos, &rules),
+            part1(&brute_force, &end_pos, &rules)
+        );
+        assert_eq!(
+            part2_junctions(&graph, &scores, &end_pos, &rules),
+            part2(&full_scores, &end_pos, &rules)
+        );
+        let tiles = best_tiles(&graph, &scores, &start_pos, &end_pos, &rules);
+        assert_eq!(
+            count_best_routes(&scores, &counts, &end_pos, &rules),
+            tiles[&start_pos].multiplicity
+        );
+    }
+    if args.iter().any(|a| a == "--render") {
+        let tiles = best_tiles(&graph, &scores, &start_pos, &end_pos, &rules);
+        let heatmap = args.iter().any(|a| a == "--heatmap");
+        print!("{}", render_best_paths(&grid, &tiles, heatmap));
+    }
+    if args.iter().any(|a| a == "--routes") {
+        println!(
+            "{} best routes",
+            count_best_routes(&scores, &counts, &end_pos, &rules)
+        );
+    }
+    if let Some(k) = arg(&args, "--k-shortest") {
+        for (score, route) in k_shortest_routes(&graph, &start_pos, &end_pos, &rules, k) {
+            let route = route
+                .iter()
+                .map(|(p, d)| format!("{},{}{}", p.x, p.y, d))
+                .collect::<Vec<_>>()
+                .join(" ");
+            println!("{score}: {route}");
+        }
+    }
+    println!("{}", part1(&scores, &end_pos, &rules));
+    println!("{}", part2_junctions(&graph, &scores, &end_pos, &rules));
 }
@@ -1,6 +1,6 @@
 use std::{cmp::Reverse, collections::BinaryHeap};
 
-use ahash::AHashSet;
+use ahash::{AHashMap, AHashSet};
 use glam::IVec2;
 
 enum Direction {
@@ -63,6 +63,10 @@ fn get_score<'a>(scores: &'a mut [Vec<Distance>], pos: &IVec2) -> &'a mut Distan
 }
 
 fn find_path(corrupt_pos: &AHashSet<IVec2>, dimensions: &IVec2) -> Distance {
+    find_path_by(dimensions, |p, _| corrupt_pos.contains(p))
+}
+
+fn find_path_by(dimensions: &IVec2, is_blocked: impl Fn(&IVec2, u32) -> bool) -> Distance {
     let mut scores =
         vec![vec![(u32::MAX, vec![]); dimensions.y as usize + 1]; dimensions.x as usize + 1];
     let mut to_visit = BinaryHeap::new();
@@ -88,7 +92,7 @@ fn find_path(corrupt_pos: &AHashSet<IVec2>, dimensions: &IVec2) -> Distance {
             let new_pos = curr.position + direction.to_vec();
             if new_pos.cmplt(start_pos).any()
                 || new_pos.cmpgt(*dimensions).any()
-                || corrupt_pos.contains(&new_pos)
+                || is_blocked(&new_pos, curr.score + 1)
             {
                 continue;
             }
@@ -112,17 +116,54 @@ fn find_path(corrupt_pos: &AHashSet<IVec2>, dimensions: &IVec2) -> Distance {
     (u32::MAX, vec![])
 }
 
-fn part1(positions: &[IVec2], dimensions: &IVec2) -> u32 {
+fn validate(positions: &[IVec2], dimensions: &IVec2, byte_count: usize) -> Result<(), String> {
+    if let Some((i, p)) = positions
+        .iter()
+        .enumerate()
+        .find(|(_, p)| p.cmplt(IVec2::ZERO).any() || p.cmpgt(*dimensions).any())
+    {
+        return Err(format!(
+            "byte {} at {},{} is outside of the {}x{} grid",
+            i + 1,
+            p.y,
+            p.x,
+            dimensions.y + 1,
+            dimensions.x + 1
+        ));
+    }
+    // the searches differ on whether a blocked start or goal counts as cut off
+    if let Some((i, p)) = positions
+        .iter()
+        .enumerate()
+        .find(|(_, p)| **p == IVec2::ZERO || *p == dimensions)
+    {
+        return Err(format!(
+            "byte {} at {},{} falls on the start or the goal",
+            i + 1,
+            p.y,
+            p.x
+        ));
+    }
+    if byte_count > positions.len() {
+        return Err(format!(
+            "{byte_count} bytes should fall, but the input only has {}",
+            positions.len()
+        ));
+    }
+    Ok(())
+}
+
+fn part1(positions: &[IVec2], dimensions: &IVec2, byte_count: usize) -> u32 {
     find_path(
-        &AHashSet::from_iter(positions[..1024].iter().map(|i| i.to_owned())),
+        &AHashSet::from_iter(positions[..byte_count].iter().map(|i| i.to_owned())),
         dimensions,
     )
     .0
 }
 
-fn part2(positions: &[IVec2], dimensions: &IVec2) -> String {
+fn part2(positions: &[IVec2], dimensions: &IVec2, byte_count: usize) -> String {
     let mut prev_path = AHashSet::new();
-    for i in 1024..positions.len() {
+    for i in byte_count..positions.len() {
         if !prev_path.is_empty() && !prev_path.contains(&positions[i - 1]) {
             continue;
         }
@@ -141,9 +182,156 @@ fn part2(positions: &[IVec2], dimensions: &IVec2) -> String {
     panic!("not found")
 }
 
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        UnionFind {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+        }
+    }
+
+    fn find(&mut self, idx: usize) -> usize {
+        let mut root = idx;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut curr = idx;
+        while self.parent[curr] != root {
+            curr = std::mem::replace(&mut self.parent[curr], root);
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
+}
+
+fn part2_union_find(positions: &[IVec2], dimensions: &IVec2) -> String {
+    // start with every byte fallen and remove them in reverse order until the
+    // start and goal end up in the same component
+
+    let width = dimensions.y as usize + 1;
+    let idx = |pos: &IVec2| pos.x as usize * width + pos.y as usize;
+    let cell_count = (dimensions.x as usize + 1) * width;
+
+    let mut fallen_at = vec![usize::MAX; cell_count];
+    for (i, pos) in positions.iter().enumerate().rev() {
+        fallen_at[idx(pos)] = i;
+    }
+
+    let mut components = UnionFind::new(cell_count);
+    let start_pos = IVec2::new(0, 0);
+    let free = |pos: &IVec2, fallen_at: &[usize], components: &mut UnionFind| {
+        for direction in Direction::all() {
+            let neighbor = pos + direction.to_vec();
+            if neighbor.cmplt(start_pos).any()
+                || neighbor.cmpgt(*dimensions).any()
+                || fallen_at[idx(&neighbor)] != usize::MAX
+            {
+                continue;
+            }
+            components.union(idx(pos), idx(&neighbor));
+        }
+    };
+    for x in 0..=dimensions.x {
+        for y in 0..=dimensions.y {
+            let pos = IVec2::new(x, y);
+            if fallen_at[idx(&pos)] == usize::MAX {
+                free(&pos, &fallen_at, &mut components);
+            }
+        }
+    }
+
+    if components.find(idx(&start_pos)) == components.find(idx(dimensions)) {
+        panic!("not found");
+    }
+    for (i, pos) in positions.iter().enumerate().rev() {
+        if fallen_at[idx(pos)] != i {
+            continue;
+        }
+        fallen_at[idx(pos)] = usize::MAX;
+        free(pos, &fallen_at, &mut components);
+        if components.find(idx(&start_pos)) == components.find(idx(dimensions)) {
+            return format!("{},{}", pos.y, pos.x);
+        }
+    }
+    panic!("not found")
+}
+
+fn part2_binary_search(positions: &[IVec2], dimensions: &IVec2) -> String {
+    // smallest prefix length that blocks the path
+    let (mut low, mut high) = (0, positions.len());
+    while low < high {
+        let mid = (low + high) / 2;
+        let corrupt_pos = AHashSet::from_iter(positions[..=mid].iter().map(|i| i.to_owned()));
+        if find_path(&corrupt_pos, dimensions).0 == u32::MAX {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    positions
+        .get(low)
+        .map(|p| format!("{},{}", p.y, p.x))
+        .expect("not found")
+}
+
+fn earliest_arrival(positions: &[IVec2], dimensions: &IVec2) -> Option<u32> {
+    // byte i (counting from 1) lands at time i, and the walker moves one cell per tick.
+    // corruption only ever grows, so arriving at a cell earlier is never worse than
+    // waiting somewhere and the score of a cell doubles as its arrival time
+
+    let mut fallen_at = AHashMap::new();
+    for (i, pos) in positions.iter().enumerate() {
+        fallen_at.entry(*pos).or_insert(i as u32 + 1);
+    }
+    let (time, _) = find_path_by(dimensions, |p, t| fallen_at.get(p).is_some_and(|f| *f <= t));
+    (time != u32::MAX).then_some(time)
+}
+
+fn arg(args: &[String], name: &str) -> Option<usize> {
+    args.iter().position(|a| a == name).map(|i| {
+        args[i + 1]
+            .parse()
+            .unwrap_or_else(|_| panic!("invalid value for {name}"))
+    })
+}
+
 fn main() {
     let input = parse(include_str!("../input.txt"));
-    let size = IVec2::new(70, 70);
-    println!("{}", part1(&input, &size));
-    println!("{}", part2(&input, &size));
+    let args: Vec<String> = std::env::args().collect();
+    let grid_size = arg(&args, "--size").unwrap_or(71) as i32;
+    let byte_count = arg(&args, "--bytes").unwrap_or(1024);
+    let size = IVec2::new(grid_size - 1, grid_size - 1);
+    if let Err(e) = validate(&input, &size, byte_count) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+    if args.iter().any(|a| a == "--check") {
+        let answer = part2(&input, &size, byte_count);
+        assert_eq!(answer, part2_union_find(&input, &size));
+        assert_eq!(answer, part2_binary_search(&input, &size));
+    }
+    if args.iter().any(|a| a == "--falling") {
+        match earliest_arrival(&input, &size) {
+            Some(time) => println!("exit reached at time {time}"),
+            None => println!("exit unreachable"),
+        }
+    }
+    println!("{}", part1(&input, &size, byte_count));
+    println!("{}", part2_union_find(&input, &size));
 }
@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 enum Color {
     White,
     Blue,
@@ -6,7 +6,9 @@ enum Color {
     Red,
     Green,
 }
-use ahash::AHashMap;
+use std::fmt::Display;
+
+use ahash::AHashSet;
 use Color::*;
 
 impl From<char> for Color {
@@ -22,6 +24,18 @@ impl From<char> for Color {
     }
 }
 
+impl Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            White => write!(f, "w"),
+            Blue => write!(f, "u"),
+            Black => write!(f, "b"),
+            Red => write!(f, "r"),
+            Green => write!(f, "g"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 struct ParsedInput {
     available_patterns: Vec<Vec<Color>>,
@@ -43,46 +57,299 @@ fn parse(input: &str) -> ParsedInput {
     }
 }
 
-fn count_possible(
+#[derive(Debug, Clone, Copy, Default)]
+struct TrieNode {
+    // child node index per color, 0 means no child as the root is never a child
+    children: [usize; 5],
+    terminal: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Trie {
+    nodes: Vec<TrieNode>,
+}
+
+impl Trie {
+    fn new(patterns: &[Vec<Color>]) -> Self {
+        let mut nodes = vec![TrieNode::default()];
+        for pattern in patterns {
+            let mut node = 0;
+            for color in pattern {
+                if nodes[node].children[*color as usize] == 0 {
+                    nodes[node].children[*color as usize] = nodes.len();
+                    nodes.push(TrieNode::default());
+                }
+                node = nodes[node].children[*color as usize];
+            }
+            nodes[node].terminal = true;
+        }
+        Trie { nodes }
+    }
+
+    fn prefix_lengths<'a>(&'a self, design: &'a [Color]) -> impl Iterator<Item = usize> + 'a {
+        design
+            .iter()
+            .scan(0, |node, color| {
+                *node = self.nodes[*node].children[*color as usize];
+                (*node != 0).then_some(*node)
+            })
+            .enumerate()
+            .filter(|(_, node)| self.nodes[*node].terminal)
+            .map(|(i, _)| i + 1)
+    }
+}
+
+trait Count: Clone + PartialEq + Display {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+}
+
+macro_rules! impl_count {
+    ($($t:ty),*) => {$(
+        impl Count for $t {
+            fn zero() -> Self {
+                0
+            }
+            fn one() -> Self {
+                1
+            }
+            fn checked_add(&self, other: &Self) -> Option<Self> {
+                <$t>::checked_add(*self, *other)
+            }
+        }
+    )*};
+}
+impl_count!(usize, u128);
+
+// little endian base 2^64 digits
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BigCount(Vec<u64>);
+
+impl Count for BigCount {
+    fn zero() -> Self {
+        BigCount(vec![])
+    }
+    fn one() -> Self {
+        BigCount(vec![1])
+    }
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        let mut digits = vec![];
+        let mut carry = false;
+        for i in 0..self.0.len().max(other.0.len()) {
+            let (sum, c1) = self
+                .0
+                .get(i)
+                .unwrap_or(&0)
+                .overflowing_add(*other.0.get(i).unwrap_or(&0));
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            digits.push(sum);
+            carry = c1 || c2;
+        }
+        if carry {
+            digits.push(1);
+        }
+        Some(BigCount(digits))
+    }
+}
+
+impl Display for BigCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // repeatedly divide by 10^19 to get decimal chunks, least significant first
+        const CHUNK: u128 = 10_000_000_000_000_000_000;
+        let mut digits = self.0.clone();
+        let mut chunks = vec![];
+        while digits.iter().any(|d| *d != 0) {
+            let mut rem = 0u128;
+            for d in digits.iter_mut().rev() {
+                let curr = (rem << 64) | *d as u128;
+                *d = (curr / CHUNK) as u64;
+                rem = curr % CHUNK;
+            }
+            chunks.push(rem as u64);
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((first, rest)) => {
+                write!(f, "{first}")?;
+                rest.iter().rev().try_for_each(|c| write!(f, "{c:019}"))
+            }
+        }
+    }
+}
+
+fn design_dp<T: Clone + PartialEq>(
     design: &[Color],
-    available_patterns: &[Vec<Color>],
-    cache: &mut AHashMap<Vec<Color>, usize>,
-) -> usize {
-    if design.is_empty() {
-        return 1;
+    trie: &Trie,
+    values: &mut Vec<T>,
+    zero: T,
+    one: T,
+    combine: impl Fn(&T, &T) -> Option<T>,
+) -> Option<T> {
+    values.clear();
+    values.resize(design.len() + 1, zero.clone());
+    values[0] = one;
+    for start in 0..design.len() {
+        if values[start] == zero {
+            continue;
+        }
+        for len in trie.prefix_lengths(&design[start..]) {
+            values[start + len] = combine(&values[start + len], &values[start])?;
+        }
     }
-    if let Some(res) = cache.get(design) {
-        return *res;
+    Some(values[design.len()].clone())
+}
+
+fn count_possible<T: Count>(design: &[Color], trie: &Trie, counts: &mut Vec<T>) -> Option<T> {
+    design_dp(design, trie, counts, T::zero(), T::one(), T::checked_add)
+}
+
+fn min_towels(design: &[Color], trie: &Trie, towels: &mut Vec<usize>) -> Option<usize> {
+    design_dp(design, trie, towels, usize::MAX, 0, |acc, towels| {
+        Some(*acc.min(&(towels + 1)))
+    })
+    .filter(|res| *res != usize::MAX)
+}
+
+fn suffix_ways(design: &[Color], trie: &Trie) -> Vec<usize> {
+    let mut ways = vec![0usize; design.len() + 1];
+    ways[design.len()] = 1;
+    for start in (0..design.len()).rev() {
+        ways[start] = trie
+            .prefix_lengths(&design[start..])
+            .map(|len| ways[start + len])
+            .fold(0, usize::saturating_add);
+    }
+    ways
+}
+
+fn decompositions<'a>(design: &'a [Color], trie: &Trie, limit: usize) -> Vec<Vec<&'a [Color]>> {
+    // only follow pieces after which the design can still be finished
+    let ways = suffix_ways(design, trie);
+
+    let mut res = vec![];
+    let mut to_visit = vec![(0, vec![])];
+    while let Some((start, pieces)) = to_visit.pop() {
+        if res.len() == limit {
+            break;
+        }
+        if start == design.len() {
+            res.push(pieces);
+            continue;
+        }
+        let lengths: Vec<usize> = trie.prefix_lengths(&design[start..]).collect();
+        for len in lengths.into_iter().rev() {
+            if ways[start + len] > 0 {
+                let mut pieces = pieces.clone();
+                pieces.push(&design[start..start + len]);
+                to_visit.push((start + len, pieces));
+            }
+        }
+    }
+    res
+}
+
+fn pattern_report(parsed_input: &ParsedInput) -> String {
+    let trie = Trie::new(&parsed_input.available_patterns);
+    let mut counts: Vec<usize> = vec![];
+    let mut res = String::new();
+
+    // a pattern that also splits into shorter patterns can be built from the others
+    let redundant: Vec<String> = parsed_input
+        .available_patterns
+        .iter()
+        .filter(|p| count_possible(p, &trie, &mut counts).is_none_or(|c| c > 1))
+        .map(|p| to_string(p))
+        .collect();
+
+    let mut used = AHashSet::new();
+    for design in &parsed_input.designs {
+        min_towels(design, &trie, &mut counts);
+        let ways = suffix_ways(design, &trie);
+        for start in (0..design.len()).filter(|s| counts[*s] != usize::MAX) {
+            for len in trie.prefix_lengths(&design[start..]) {
+                if ways[start + len] > 0 {
+                    used.insert(&design[start..start + len]);
+                }
+            }
+        }
     }
-    let count = available_patterns
+    let unused: Vec<String> = parsed_input
+        .available_patterns
         .iter()
-        .filter(|p| design.starts_with(p))
-        .map(|p| count_possible(&design[p.len()..], available_patterns, cache))
-        .sum();
-    cache.insert(design.to_vec(), count);
-    count
+        .filter(|p| !used.contains(p.as_slice()))
+        .map(|p| to_string(p))
+        .collect();
+
+    for design in &parsed_input.designs {
+        match min_towels(design, &trie, &mut counts) {
+            Some(towels) => res += &format!("{}: {towels} towels\n", to_string(design)),
+            None => res += &format!("{}: impossible\n", to_string(design)),
+        }
+    }
+    res += &format!(
+        "{} redundant patterns: {}\n",
+        redundant.len(),
+        redundant.join(", ")
+    );
+    res += &format!("{} unused patterns: {}\n", unused.len(), unused.join(", "));
+    res
 }
 
 fn part1(parsed_input: &ParsedInput) -> usize {
-    let mut cache = AHashMap::new();
+    let trie = Trie::new(&parsed_input.available_patterns);
+    let mut towels = vec![];
     parsed_input
         .designs
         .iter()
-        .filter(|d| count_possible(d, &parsed_input.available_patterns, &mut cache) > 0)
+        .filter(|d| min_towels(d, &trie, &mut towels).is_some())
         .count()
 }
 
-fn part2(parsed_input: &ParsedInput) -> usize {
-    let mut cache = AHashMap::new();
-    parsed_input
-        .designs
-        .iter()
-        .map(|d| count_possible(d, &parsed_input.available_patterns, &mut cache))
-        .sum()
+fn part2<T: Count>(parsed_input: &ParsedInput) -> Result<T, String> {
+    let trie = Trie::new(&parsed_input.available_patterns);
+    let mut counts = vec![];
+    parsed_input.designs.iter().try_fold(T::zero(), |acc, d| {
+        count_possible(d, &trie, &mut counts)
+            .and_then(|count| acc.checked_add(&count))
+            .ok_or_else(|| format!("arrangement count overflows at design {}", to_string(d)))
+    })
+}
+
+fn to_string(colors: &[Color]) -> String {
+    colors.iter().map(|c| c.to_string()).collect()
 }
 
 fn main() {
     let input = parse(include_str!("../input.txt"));
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|a| a == "--decompositions") {
+        let limit = args[i + 1].parse().unwrap();
+        let trie = Trie::new(&input.available_patterns);
+        for design in &input.designs {
+            println!("{}:", to_string(design));
+            for pieces in decompositions(design, &trie, limit) {
+                let pieces: Vec<String> = pieces.iter().map(|p| to_string(p)).collect();
+                println!("  {}", pieces.join(" "));
+            }
+        }
+    }
+    if args.iter().any(|a| a == "--report") {
+        print!("{}", pattern_report(&input));
+    }
     println!("{}", part1(&input));
-    println!("{}", part2(&input));
+    let res = if args.iter().any(|a| a == "--u128") {
+        part2::<u128>(&input).map(|c| c.to_string())
+    } else if args.iter().any(|a| a == "--bigint") {
+        part2::<BigCount>(&input).map(|c| c.to_string())
+    } else {
+        part2::<usize>(&input).map(|c| c.to_string())
+    };
+    match res {
+        Ok(count) => println!("{count}"),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
 }
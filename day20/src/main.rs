@@ -1,3 +1,11 @@
+use std::{
+    borrow::Cow,
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+    str::FromStr,
+};
+
+use ahash::AHashSet;
 use glam::IVec2;
 use rayon::prelude::*;
 
@@ -37,7 +45,6 @@ impl Direction {
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 struct Tile {
     tiletype: bool,
-    distance: usize,
 }
 
 fn parse(input: &str) -> (Vec<Vec<Tile>>, IVec2, IVec2) {
@@ -65,7 +72,6 @@ fn parse(input: &str) -> (Vec<Vec<Tile>>, IVec2, IVec2) {
                             }
                             _ => false,
                         },
-                        distance: usize::MAX,
                     })
                     .collect()
             })
@@ -75,65 +81,349 @@ fn parse(input: &str) -> (Vec<Vec<Tile>>, IVec2, IVec2) {
     )
 }
 
-fn get_distances(
-    map: &mut [Vec<Tile>],
-    pos: &IVec2,
-    distance: &usize,
-    path: &mut Vec<IVec2>,
-    end_pos: &IVec2,
-) {
-    let curr_tile = &mut map[pos.x as usize][pos.y as usize];
-    curr_tile.distance = *distance;
-    if pos == end_pos {
-        return;
-    }
-    for direction in Direction::all() {
-        let new_tile_pos = pos + direction.to_vec();
-        let new_tile = map[new_tile_pos.x as usize][new_tile_pos.y as usize];
-        if !new_tile.tiletype && new_tile.distance > distance + 1 {
-            path.push(new_tile_pos);
-            get_distances(map, &new_tile_pos, &(distance + 1), path, end_pos);
-            break;
+fn get_distances(map: &[Vec<Tile>], from: &IVec2) -> Vec<Vec<usize>> {
+    // bfs over the track
+    let mut distances = vec![vec![usize::MAX; map[0].len()]; map.len()];
+    let mut to_visit = VecDeque::from([*from]);
+    distances[from.x as usize][from.y as usize] = 0;
+    while let Some(pos) = to_visit.pop_front() {
+        let distance = distances[pos.x as usize][pos.y as usize];
+        for direction in Direction::all() {
+            let new_pos = pos + direction.to_vec();
+            let Some(tile) = map
+                .get(new_pos.x as usize)
+                .and_then(|l| l.get(new_pos.y as usize))
+            else {
+                continue;
+            };
+            if !tile.tiletype && distances[new_pos.x as usize][new_pos.y as usize] == usize::MAX {
+                distances[new_pos.x as usize][new_pos.y as usize] = distance + 1;
+                to_visit.push_back(new_pos);
+            }
         }
     }
+    distances
 }
 
-fn part12(map: &[Vec<Tile>], path: &[IVec2], max_distance: i32) -> usize {
-    let combinations: Vec<(i32, i32)> = (1..=max_distance)
-        .flat_map(|i| (0..=(max_distance - i)).map(move |j| (i, j)))
-        .collect();
-    path.par_iter()
-        .map(|tile| {
-            Direction::all()
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+struct Cheat {
+    start: IVec2,
+    end: IVec2,
+    saving: usize,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Metric {
+    Manhattan,
+    Chebyshev,
+}
+
+impl Metric {
+    fn distance(self, offset: IVec2) -> usize {
+        let offset = offset.abs();
+        match self {
+            Metric::Manhattan => (offset.x + offset.y) as usize,
+            Metric::Chebyshev => offset.x.max(offset.y) as usize,
+        }
+    }
+
+    fn moves(self) -> Vec<IVec2> {
+        let straight = Direction::all().into_iter().map(|d| d.to_vec());
+        match self {
+            Metric::Manhattan => straight.collect(),
+            Metric::Chebyshev => straight
+                .chain(
+                    Direction::all()
+                        .into_iter()
+                        .map(|d| d.to_vec() + d.turn().to_vec()),
+                )
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "manhattan" => Ok(Metric::Manhattan),
+            "chebyshev" => Ok(Metric::Chebyshev),
+            _ => Err(format!("invalid metric: {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CheatRules {
+    max_distance: i32,
+    metric: Metric,
+    wall_only: bool,
+    min_saving: usize,
+}
+
+struct RaceTrack {
+    walls: Vec<Vec<bool>>,
+    dist_start: Vec<Vec<usize>>,
+    dist_end: Vec<Vec<usize>>,
+    best_time: usize,
+    track: Vec<IVec2>,
+}
+
+impl RaceTrack {
+    fn new(map: &[Vec<Tile>], start_pos: &IVec2, end_pos: &IVec2) -> Self {
+        let dist_start = get_distances(map, start_pos);
+        let dist_end = get_distances(map, end_pos);
+        let best_time = dist_start[end_pos.x as usize][end_pos.y as usize];
+        let track = (0..dist_start.len())
+            .flat_map(|x| (0..dist_start[x].len()).map(move |y| IVec2::new(x as i32, y as i32)))
+            .filter(|p| dist_start[p.x as usize][p.y as usize] != usize::MAX)
+            .collect();
+        RaceTrack {
+            walls: map
+                .iter()
+                .map(|l| l.iter().map(|t| t.tiletype).collect())
+                .collect(),
+            dist_start,
+            dist_end,
+            best_time,
+            track,
+        }
+    }
+
+    fn cheats_from<'a>(
+        &'a self,
+        tile: &IVec2,
+        offsets: &'a [(IVec2, usize)],
+        min_saving: usize,
+    ) -> impl Iterator<Item = Cheat> + 'a {
+        let start = *tile;
+        let start_distance = self.dist_start[tile.x as usize][tile.y as usize];
+        offsets.iter().filter_map(move |(offset, cheat_len)| {
+            let end = start + offset;
+            let end_distance = *self
+                .dist_end
+                .get(end.x as usize)
+                .and_then(|l| l.get(end.y as usize))
+                .filter(|d| **d != usize::MAX)?;
+            let time = start_distance + cheat_len + end_distance;
+            (time + min_saving <= self.best_time).then(|| Cheat {
+                start,
+                end,
+                saving: self.best_time - time,
+            })
+        })
+    }
+
+    fn is_wall(&self, pos: &IVec2) -> bool {
+        self.walls
+            .get(pos.x as usize)
+            .and_then(|l| l.get(pos.y as usize))
+            .is_some_and(|w| *w)
+    }
+
+    fn wall_offsets(&self, tile: &IVec2, rules: &CheatRules) -> Vec<(IVec2, usize)> {
+        // bfs through walls only, every track tile reached from a wall ends a cheat
+        let moves = rules.metric.moves();
+        let mut seen = AHashSet::from([*tile]);
+        let mut offsets = vec![];
+        let mut to_visit = VecDeque::from([(*tile, 0)]);
+        while let Some((pos, cheat_len)) = to_visit.pop_front() {
+            if cheat_len == rules.max_distance as usize {
+                continue;
+            }
+            for new_pos in moves.iter().map(|m| pos + m) {
+                if !seen.insert(new_pos) {
+                    continue;
+                }
+                if self.is_wall(&new_pos) {
+                    to_visit.push_back((new_pos, cheat_len + 1));
+                } else if pos != *tile {
+                    offsets.push((new_pos - tile, cheat_len + 1));
+                }
+            }
+        }
+        offsets
+    }
+
+    fn offsets_for<'a>(
+        &self,
+        tile: &IVec2,
+        rules: &CheatRules,
+        all_offsets: &'a [(IVec2, usize)],
+    ) -> Cow<'a, [(IVec2, usize)]> {
+        if rules.wall_only {
+            Cow::Owned(self.wall_offsets(tile, rules))
+        } else {
+            Cow::Borrowed(all_offsets)
+        }
+    }
+
+    fn fastest_with_budget(&self, budget: usize, metric: Metric) -> usize {
+        // dijkstra over (tile, used cheat time), finishing honestly from any state
+        let offsets = cheat_offsets(budget as i32, metric);
+        let mut index = vec![vec![usize::MAX; self.walls[0].len()]; self.walls.len()];
+        for (i, p) in self.track.iter().enumerate() {
+            index[p.x as usize][p.y as usize] = i;
+        }
+        let track_index = |pos: IVec2| {
+            index
+                .get(pos.x as usize)
+                .and_then(|l| l.get(pos.y as usize))
+                .copied()
+                .filter(|i| *i != usize::MAX)
+        };
+        let start = self
+            .track
+            .iter()
+            .position(|p| self.dist_start[p.x as usize][p.y as usize] == 0)
+            .unwrap();
+        // states pop in time order, so a tile settled earlier with no more cheat time
+        // used dominates every later state on it
+        let mut least_used = vec![usize::MAX; self.track.len()];
+        let mut to_visit = BinaryHeap::from([Reverse((0, 0, start))]);
+        let mut fastest = self.best_time;
+        while let Some(Reverse((time, used, idx))) = to_visit.pop() {
+            if time >= fastest || least_used[idx] <= used {
+                continue;
+            }
+            least_used[idx] = used;
+            let pos = self.track[idx];
+            fastest = fastest.min(time + self.dist_end[pos.x as usize][pos.y as usize]);
+            let steps = Direction::all().into_iter().map(|d| (d.to_vec(), 0));
+            let cheats = offsets
                 .iter()
-                .map(|direction| {
-                    combinations
-                        .iter()
-                        .filter(|(i, j)| {
-                            let new_tile_pos =
-                                tile + i * direction.to_vec() + j * direction.turn().to_vec();
-                            map.get(new_tile_pos.x as usize)
-                                .and_then(|l| l.get(new_tile_pos.y as usize))
-                                .map_or(false, |t| {
-                                    t.distance
-                                        >= map[tile.x as usize][tile.y as usize].distance
-                                            + 100
-                                            + *i as usize
-                                            + *j as usize
-                                        && !t.tiletype
-                                })
-                        })
-                        .count()
-                })
-                .sum::<usize>()
+                .filter(|(_, cheat_len)| used + cheat_len <= budget)
+                .copied();
+            for (offset, cheat_len) in steps.chain(cheats) {
+                if let Some(new_idx) = track_index(pos + offset) {
+                    if least_used[new_idx] > used + cheat_len {
+                        let cost = cheat_len.max(1);
+                        to_visit.push(Reverse((time + cost, used + cheat_len, new_idx)));
+                    }
+                }
+            }
+        }
+        fastest
+    }
+}
+
+fn cheat_offsets(max_distance: i32, metric: Metric) -> Vec<(IVec2, usize)> {
+    (-max_distance..=max_distance)
+        .flat_map(|i| (-max_distance..=max_distance).map(move |j| IVec2::new(i, j)))
+        .map(|offset| (offset, metric.distance(offset)))
+        .filter(|(_, cheat_len)| (1..=max_distance as usize).contains(cheat_len))
+        .collect()
+}
+
+fn part12(race_track: &RaceTrack, rules: &CheatRules) -> usize {
+    let offsets = cheat_offsets(rules.max_distance, rules.metric);
+    race_track
+        .track
+        .par_iter()
+        .map(|tile| {
+            let offsets = race_track.offsets_for(tile, rules, &offsets);
+            race_track
+                .cheats_from(tile, &offsets, rules.min_saving)
+                .count()
         })
         .sum()
 }
 
+fn find_cheats(race_track: &RaceTrack, rules: &CheatRules) -> Vec<Cheat> {
+    let offsets = cheat_offsets(rules.max_distance, rules.metric);
+    let mut cheats: Vec<Cheat> = race_track
+        .track
+        .par_iter()
+        .flat_map_iter(|tile| {
+            let offsets = race_track.offsets_for(tile, rules, &offsets);
+            race_track
+                .cheats_from(tile, &offsets, rules.min_saving)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    cheats.sort_unstable_by_key(|c| (c.saving, c.start.x, c.start.y, c.end.x, c.end.y));
+    cheats
+}
+
+fn savings_report(cheats: &[Cheat]) -> String {
+    cheats
+        .chunk_by(|a, b| a.saving == b.saving)
+        .map(|group| match group.len() {
+            1 => format!(
+                "There is one cheat that saves {} picoseconds.\n",
+                group[0].saving
+            ),
+            n => format!(
+                "There are {n} cheats that save {} picoseconds.\n",
+                group[0].saving
+            ),
+        })
+        .collect()
+}
+
+fn draw_cheat(map: &[Vec<Tile>], cheat: &Cheat) -> String {
+    let mut res = String::new();
+    for (l_idx, l) in map.iter().enumerate() {
+        for (c_idx, t) in l.iter().enumerate() {
+            let pos = IVec2::new(l_idx as i32, c_idx as i32);
+            res.push(match (pos == cheat.start, pos == cheat.end, t.tiletype) {
+                (true, _, _) => '1',
+                (_, true, _) => '2',
+                (_, _, true) => '#',
+                _ => '.',
+            });
+        }
+        res.push('\n');
+    }
+    res
+}
+
+fn arg<T: FromStr>(args: &[String], name: &str) -> Option<T> {
+    args.iter().position(|a| a == name).map(|i| {
+        args[i + 1]
+            .parse()
+            .unwrap_or_else(|_| panic!("invalid value for {name}"))
+    })
+}
+
 fn main() {
-    let (mut map, start_pos, end_pos) = parse(include_str!("../input.txt"));
-    let mut path = vec![start_pos];
-    get_distances(&mut map, &start_pos, &0, &mut path, &end_pos);
-    println!("{}", part12(&map, &path, 2));
-    println!("{}", part12(&map, &path, 20));
+    let (map, start_pos, end_pos) = parse(include_str!("../input.txt"));
+    let race_track = RaceTrack::new(&map, &start_pos, &end_pos);
+    let args: Vec<String> = std::env::args().collect();
+    let min_saving = arg(&args, "--min-saving").unwrap_or(100);
+    let metric = arg(&args, "--metric").unwrap_or(Metric::Manhattan);
+    if let Some(budget) = arg(&args, "--budget") {
+        let fastest = race_track.fastest_with_budget(budget, metric);
+        println!(
+            "fastest race with {budget} picoseconds of cheating: {fastest} (saves {})",
+            race_track.best_time - fastest
+        );
+    }
+    for max_distance in [2, 20] {
+        let rules = CheatRules {
+            max_distance,
+            metric,
+            wall_only: args.iter().any(|a| a == "--wall-only"),
+            min_saving,
+        };
+        if args.iter().any(|a| a == "--report" || a == "--list") {
+            let cheats = find_cheats(&race_track, &rules);
+            if args.iter().any(|a| a == "--report") {
+                print!("{}", savings_report(&cheats));
+            }
+            if args.iter().any(|a| a == "--list") {
+                for cheat in &cheats {
+                    println!(
+                        "{},{} -> {},{} saves {}",
+                        cheat.start.y, cheat.start.x, cheat.end.y, cheat.end.x, cheat.saving
+                    );
+                    if args.iter().any(|a| a == "--draw") {
+                        println!("{}", draw_cheat(&map, cheat));
+                    }
+                }
+            }
+        }
+        println!("{}", part12(&race_track, &rules));
+    }
 }
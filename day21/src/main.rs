@@ -1,13 +1,48 @@
-use std::{collections::HashMap, fmt::Display, iter};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    iter,
+    ops::{Add, Mul},
+};
 
 use glam::IVec2;
 use itertools::Itertools;
-use phf::phf_map;
 
-#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
-enum Pad {
-    Keypad,
-    Numpad,
+const NUMPAD_LAYOUT: &str = "789\n456\n123\n 0A";
+const KEYPAD_LAYOUT: &str = " ^A\n<v>";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pad {
+    positions: HashMap<char, IVec2>,
+    gaps: Vec<IVec2>,
+}
+
+impl Pad {
+    fn from_layout(layout: &str) -> Self {
+        let width = layout.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+        let mut positions = HashMap::new();
+        let mut gaps = vec![];
+        for (l_idx, l) in layout.lines().enumerate() {
+            let keys = l.chars().chain(iter::repeat(' ')).take(width);
+            for (c_idx, c) in keys.enumerate() {
+                let pos = IVec2::new(l_idx as i32, c_idx as i32);
+                if c == ' ' {
+                    gaps.push(pos);
+                } else if positions.insert(c, pos).is_some() {
+                    panic!("key {c} appears twice in layout");
+                }
+            }
+        }
+        if !positions.contains_key(&'A') {
+            panic!("layout has no A key");
+        }
+        Pad { positions, gaps }
+    }
+
+    fn can_control(&self) -> bool {
+        "^v<>A".chars().all(|c| self.positions.contains_key(&c))
+    }
 }
 
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
@@ -40,126 +75,525 @@ impl Display for Direction {
     }
 }
 
-static NUMPAD_POSITIONS: phf::Map<char, IVec2> = phf_map! {
-    '7' => IVec2::new(0, 0),
-    '8' => IVec2::new(0, 1),
-    '9' => IVec2::new(0, 2),
-    '4' => IVec2::new(1, 0),
-    '5' => IVec2::new(1, 1),
-    '6' => IVec2::new(1, 2),
-    '1' => IVec2::new(2, 0),
-    '2' => IVec2::new(2, 1),
-    '3' => IVec2::new(2, 2),
-    '0' => IVec2::new(3, 1),
-    'A' => IVec2::new(3, 2),
-};
-
-static KEYPAD_POSITIONS: phf::Map<char, IVec2> = phf_map! {
-    '^' => IVec2::new(0, 1),
-    'A' => IVec2::new(0, 2),
-    '<' => IVec2::new(1, 0),
-    'v' => IVec2::new(1, 1),
-    '>' => IVec2::new(1, 2),
-};
-
 fn parse(input: &str) -> Vec<String> {
     input.lines().map(|l| l.to_string()).collect()
 }
 
-fn directions(pos1: &IVec2, pos2: &IVec2) -> Vec<Vec<Direction>> {
-    let diff = pos2 - pos1;
-    let mut path_directions = vec![];
-    if diff.x > 0 {
-        path_directions.extend((0..diff.x).map(|_| Direction::Down));
-    } else {
-        path_directions.extend((0..diff.x.abs()).map(|_| Direction::Up));
-    }
-    if diff.y > 0 {
-        path_directions.extend((0..diff.y).map(|_| Direction::Right));
-    } else {
-        path_directions.extend((0..diff.y.abs()).map(|_| Direction::Left));
-    }
-    let reverse = path_directions.iter().rev().map(|d| d.to_owned()).collect();
-    if reverse == path_directions {
-        vec![path_directions]
-    } else {
-        vec![path_directions, reverse]
+fn get_paths(pad: &Pad, start: char, end: char) -> Vec<Vec<Direction>> {
+    // every shortest path between the keys, gaps can force a detour so bfs back from the end
+    let pos1 = pad.positions.get(&start).unwrap();
+    let pos2 = pad
+        .positions
+        .get(&end)
+        .unwrap_or_else(|| panic!("key {end} is not on the pad"));
+    let all_directions = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+    let mut dist_end = HashMap::from([(*pos2, 0)]);
+    let mut to_visit = VecDeque::from([*pos2]);
+    while let Some(pos) = to_visit.pop_front() {
+        for direction in all_directions {
+            let new_pos = pos + direction.to_vec();
+            if pad.positions.values().any(|p| *p == new_pos) && !dist_end.contains_key(&new_pos) {
+                dist_end.insert(new_pos, dist_end[&pos] + 1);
+                to_visit.push_back(new_pos);
+            }
+        }
     }
-}
-
-fn is_allowed_path(pos1: &IVec2, directions: &[Direction], banned_pos: &IVec2) -> bool {
-    let mut pos = *pos1;
-    for direction in directions {
-        pos += direction.to_vec();
-        if pos == *banned_pos {
-            return false;
+    let Some(len) = dist_end.get(pos1) else {
+        return vec![];
+    };
+    let mut paths = vec![(*pos1, vec![])];
+    for remaining in (0..*len).rev() {
+        let mut next_paths = vec![];
+        for (pos, path) in paths {
+            for direction in all_directions {
+                let new_pos = pos + direction.to_vec();
+                if dist_end.get(&new_pos) == Some(&remaining) {
+                    let mut path = path.clone();
+                    path.push(direction);
+                    next_paths.push((new_pos, path));
+                }
+            }
         }
+        paths = next_paths;
     }
-    true
+    paths.into_iter().map(|(_, path)| path).collect()
 }
 
-fn get_paths(pos1: &IVec2, pos2: &IVec2, banned_pos: &IVec2) -> Vec<Vec<Direction>> {
-    directions(pos1, pos2)
-        .iter()
-        .filter(|d| is_allowed_path(pos1, d, banned_pos))
-        .map(|d| d.to_owned())
-        .collect()
-}
-
-fn min_length(code: &str, pads: &[Pad], cache: &mut HashMap<(String, usize), usize>) -> usize {
+fn min_length(
+    code: &str,
+    pads: &[Pad],
+    cache: &mut HashMap<(String, usize), Option<usize>>,
+) -> Option<usize> {
     // credits: RubixDev
 
     if pads.is_empty() {
-        return code.len();
+        return Some(code.len());
     }
     if let Some(val) = cache.get(&(code.to_string(), pads.len())) {
         return *val;
     }
+    // every key press ends on A, so the segments between them are independent
     let result = std::iter::once('A')
         .chain(code.chars())
         .tuple_windows()
         .map(|(start, end)| {
-            match pads[0] {
-                Pad::Numpad => get_paths(
-                    NUMPAD_POSITIONS.get(&start).unwrap(),
-                    NUMPAD_POSITIONS.get(&end).unwrap(),
-                    &IVec2::new(3, 0),
-                ),
-                Pad::Keypad => get_paths(
-                    KEYPAD_POSITIONS.get(&start).unwrap(),
-                    KEYPAD_POSITIONS.get(&end).unwrap(),
-                    &IVec2::new(0, 0),
-                ),
-            }
-            .into_iter()
-            .map(|dirs| dirs.iter().map(|d| d.to_string()).collect::<String>() + "A")
-        })
-        .multi_cartesian_product()
-        .map(|combination| {
-            combination
-                .iter()
-                .map(|c| min_length(c, &pads[1..], cache))
-                .sum::<usize>()
+            get_paths(&pads[0], start, end)
+                .into_iter()
+                .map(|dirs| dirs.iter().map(|d| d.to_string()).collect::<String>() + "A")
+                .filter_map(|c| min_length(&c, &pads[1..], cache))
+                .min()
         })
-        .min()
-        .unwrap();
+        .sum();
     cache.insert((code.to_string(), pads.len()), result);
     result
 }
 
-fn part12(input: &[String], keypad_robot_count: usize) -> usize {
+fn min_sequence(
+    code: &str,
+    pads: &[Pad],
+    cache: &mut HashMap<(String, usize), Option<String>>,
+) -> Option<String> {
+    if pads.is_empty() {
+        return Some(code.to_string());
+    }
+    if let Some(val) = cache.get(&(code.to_string(), pads.len())) {
+        return val.clone();
+    }
+    let result = std::iter::once('A')
+        .chain(code.chars())
+        .tuple_windows()
+        .map(|(start, end)| {
+            get_paths(&pads[0], start, end)
+                .into_iter()
+                .map(|dirs| dirs.iter().map(|d| d.to_string()).collect::<String>() + "A")
+                .filter_map(|c| min_sequence(&c, &pads[1..], cache))
+                .min_by_key(|s| s.len())
+        })
+        .collect::<Option<String>>();
+    cache.insert((code.to_string(), pads.len()), result.clone());
+    result
+}
+
+fn simulate(sequence: &str, pads: &[Pad]) -> Result<String, String> {
+    // the presses drive the robot at the last pad, whose presses drive the one before
+    let mut presses = sequence.to_string();
+    for (pad_idx, pad) in pads.iter().enumerate().rev() {
+        let mut pos = pad.positions[&'A'];
+        let mut typed = String::new();
+        for press in presses.chars() {
+            let direction = match press {
+                '^' => Direction::Up,
+                'v' => Direction::Down,
+                '<' => Direction::Left,
+                '>' => Direction::Right,
+                'A' => {
+                    typed.push(*pad.positions.iter().find(|(_, p)| **p == pos).unwrap().0);
+                    continue;
+                }
+                c => return Err(format!("invalid press {c} for pad {}", pad_idx + 1)),
+            };
+            pos += direction.to_vec();
+            if !pad.positions.values().any(|p| *p == pos) {
+                return Err(format!(
+                    "robot at pad {} points at a gap after typing {typed}",
+                    pad_idx + 1
+                ));
+            }
+        }
+        presses = typed;
+    }
+    Ok(presses)
+}
+
+fn code_value(code: &str) -> usize {
+    code.chars()
+        .filter(char::is_ascii_digit)
+        .collect::<String>()
+        .parse()
+        .unwrap_or(0)
+}
+
+fn part12(input: &[String], pads: &[Pad]) -> Option<usize> {
     let mut cache = HashMap::new();
-    let pads: Vec<Pad> = iter::once(Pad::Numpad)
-        .chain((0..keypad_robot_count).map(|_| Pad::Keypad))
-        .collect();
     input
         .iter()
-        .map(|s| s[..s.len() - 1].parse::<usize>().unwrap() * min_length(s, &pads, &mut cache))
+        .map(|s| min_length(s, pads, &mut cache).map(|l| code_value(s) * l))
         .sum()
 }
 
+// little endian base 2^64 digits without leading zeros, press counts outgrow u128
+// after about 85 keypad robots
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct BigCount(Vec<u64>);
+
+impl From<u128> for BigCount {
+    fn from(n: u128) -> Self {
+        let mut digits = vec![n as u64, (n >> 64) as u64];
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        BigCount(digits)
+    }
+}
+
+impl Add<&BigCount> for BigCount {
+    type Output = BigCount;
+
+    fn add(self, other: &BigCount) -> BigCount {
+        let mut digits = vec![];
+        let mut carry = false;
+        for i in 0..self.0.len().max(other.0.len()) {
+            let (sum, c1) = self
+                .0
+                .get(i)
+                .unwrap_or(&0)
+                .overflowing_add(*other.0.get(i).unwrap_or(&0));
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            digits.push(sum);
+            carry = c1 || c2;
+        }
+        if carry {
+            digits.push(1);
+        }
+        BigCount(digits)
+    }
+}
+
+impl Mul<u64> for BigCount {
+    type Output = BigCount;
+
+    fn mul(self, factor: u64) -> BigCount {
+        if factor == 0 {
+            return BigCount::default();
+        }
+        let mut digits = vec![];
+        let mut carry = 0u128;
+        for d in self.0 {
+            let product = d as u128 * factor as u128 + carry;
+            digits.push(product as u64);
+            carry = product >> 64;
+        }
+        if carry > 0 {
+            digits.push(carry as u64);
+        }
+        BigCount(digits)
+    }
+}
+
+impl Ord for BigCount {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigCount {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for BigCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // repeatedly divide by 10^19 to get decimal chunks, least significant first
+        const CHUNK: u128 = 10_000_000_000_000_000_000;
+        let mut digits = self.0.clone();
+        let mut chunks = vec![];
+        while digits.iter().any(|d| *d != 0) {
+            let mut rem = 0u128;
+            for d in digits.iter_mut().rev() {
+                let curr = (rem << 64) | *d as u128;
+                *d = (curr / CHUNK) as u64;
+                rem = curr % CHUNK;
+            }
+            chunks.push(rem as u64);
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((first, rest)) => {
+                write!(f, "{first}")?;
+                rest.iter().rev().try_for_each(|c| write!(f, "{c:019}"))
+            }
+        }
+    }
+}
+
+trait Presses: Clone + Ord + Display {
+    fn from_count(n: u64) -> Self;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, factor: u64) -> Option<Self>;
+}
+
+impl Presses for u128 {
+    fn from_count(n: u64) -> Self {
+        n as u128
+    }
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        u128::checked_add(*self, *other)
+    }
+    fn checked_mul(&self, factor: u64) -> Option<Self> {
+        u128::checked_mul(*self, factor as u128)
+    }
+}
+
+impl Presses for BigCount {
+    fn from_count(n: u64) -> Self {
+        BigCount::from(n as u128)
+    }
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self.clone() + other)
+    }
+    fn checked_mul(&self, factor: u64) -> Option<Self> {
+        Some(self.clone() * factor)
+    }
+}
+
+// costs[from][to] per key pair, None if no path joins the keys
+type PairCosts<T> = Vec<Vec<Option<T>>>;
+
+enum Costs {
+    Small(PairCosts<u128>),
+    Big(PairCosts<BigCount>),
+}
+
+fn to_big(costs: &PairCosts<u128>) -> PairCosts<BigCount> {
+    costs
+        .iter()
+        .map(|l| l.iter().map(|c| c.map(BigCount::from)).collect())
+        .collect()
+}
+
+struct PadTable {
+    index: HashMap<char, usize>,
+    // paths[from][to] lists all gap free shortest paths between two keys
+    paths: Vec<Vec<Vec<Vec<Direction>>>>,
+    // key index of ^ v < > (in the order of Direction) and A
+    direction_keys: [usize; 4],
+    activate_key: usize,
+}
+
+impl PadTable {
+    fn new(pad: &Pad) -> Self {
+        let keys: Vec<char> = pad.positions.keys().copied().sorted_unstable().collect();
+        let index: HashMap<char, usize> = keys.iter().enumerate().map(|(i, k)| (*k, i)).collect();
+        let paths = keys
+            .iter()
+            .map(|from| keys.iter().map(|to| get_paths(pad, *from, *to)).collect())
+            .collect();
+        let key = |c: char| index.get(&c).copied().unwrap_or(usize::MAX);
+        PadTable {
+            direction_keys: [key('^'), key('v'), key('<'), key('>')],
+            activate_key: key('A'),
+            index,
+            paths,
+        }
+    }
+}
+
+// None if a cost doesn't fit in T
+fn layer_costs<T: Presses>(
+    table: &PadTable,
+    upper: Option<(&PadTable, &PairCosts<T>)>,
+) -> Option<PairCosts<T>> {
+    let mut costs = vec![vec![None; table.index.len()]; table.index.len()];
+    for (from, to_paths) in table.paths.iter().enumerate() {
+        for (to, paths) in to_paths.iter().enumerate() {
+            for path in paths {
+                let cost = match upper {
+                    None => Some(T::from_count(path.len() as u64 + 1)),
+                    Some((upper, upper_costs)) => {
+                        let mut prev = upper.activate_key;
+                        let mut cost = Some(T::from_count(0));
+                        let keys = path
+                            .iter()
+                            .map(|d| upper.direction_keys[*d as usize])
+                            .chain(iter::once(upper.activate_key));
+                        for key in keys {
+                            cost = match (cost, &upper_costs[prev][key]) {
+                                (Some(cost), Some(step)) => Some(cost.checked_add(step)?),
+                                _ => None,
+                            };
+                            prev = key;
+                        }
+                        cost
+                    }
+                };
+                if cost.is_some() && (costs[from][to].is_none() || cost < costs[from][to]) {
+                    costs[from][to] = cost;
+                }
+            }
+        }
+    }
+    Some(costs)
+}
+
+fn pair_costs(pads: &[Pad]) -> (PadTable, Costs) {
+    // costs[from][to] is the number of presses on the human keypad to move the robot
+    // at a pad from one key to another and press it, filled in from the human upwards.
+    // the counts stay in u128 until a layer overflows it
+    let mut tables: Vec<(&Pad, PadTable)> = vec![];
+    for pad in pads {
+        if !tables.iter().any(|(p, _)| *p == pad) {
+            tables.push((pad, PadTable::new(pad)));
+        }
+    }
+    let table = |pad: &Pad| &tables.iter().find(|(p, _)| *p == pad).unwrap().1;
+
+    let mut upper_costs: Option<Costs> = None;
+    for (pad_idx, pad) in pads.iter().enumerate().rev() {
+        let curr_table = table(pad);
+        let upper_table = pads.get(pad_idx + 1).map(table);
+        let big = |upper_costs: Option<&PairCosts<BigCount>>| {
+            let costs = layer_costs(curr_table, upper_table.zip(upper_costs));
+            Costs::Big(costs.expect("big counts can't overflow"))
+        };
+        upper_costs = Some(match &upper_costs {
+            None => match layer_costs::<u128>(curr_table, None) {
+                Some(costs) => Costs::Small(costs),
+                None => big(None),
+            },
+            Some(Costs::Small(costs)) => {
+                match layer_costs(curr_table, upper_table.zip(Some(costs))) {
+                    Some(costs) => Costs::Small(costs),
+                    None => big(Some(&to_big(costs))),
+                }
+            }
+            Some(Costs::Big(costs)) => big(Some(costs)),
+        });
+    }
+    let first = tables.swap_remove(0).1;
+    (first, upper_costs.expect("a chain needs at least one pad"))
+}
+
+// Ok(None) if the total doesn't fit in T
+fn complexity<T: Presses>(
+    input: &[String],
+    table: &PadTable,
+    costs: &PairCosts<T>,
+) -> Result<Option<T>, String> {
+    let mut total = T::from_count(0);
+    for code in input {
+        let mut presses = T::from_count(0);
+        for (start, end) in iter::once('A').chain(code.chars()).tuple_windows() {
+            let key = |c: char| {
+                table
+                    .index
+                    .get(&c)
+                    .ok_or(format!("key {c} of code {code} is not on the pad"))
+            };
+            let cost = costs[*key(start)?][*key(end)?]
+                .as_ref()
+                .ok_or(format!("code {code} needs a path from {start} to {end}"))?;
+            let Some(sum) = presses.checked_add(cost) else {
+                return Ok(None);
+            };
+            presses = sum;
+        }
+        let Some(sum) = presses
+            .checked_mul(code_value(code) as u64)
+            .and_then(|c| total.checked_add(&c))
+        else {
+            return Ok(None);
+        };
+        total = sum;
+    }
+    Ok(Some(total))
+}
+
+fn part12_table(input: &[String], pads: &[Pad]) -> Result<BigCount, String> {
+    let (table, costs) = pair_costs(pads);
+    let big = |costs: &PairCosts<BigCount>| {
+        complexity(input, &table, costs).map(|c| c.expect("big counts can't overflow"))
+    };
+    match costs {
+        Costs::Small(costs) => match complexity(input, &table, &costs)? {
+            Some(total) => Ok(BigCount::from(total)),
+            None => big(&to_big(&costs)),
+        },
+        Costs::Big(costs) => big(&costs),
+    }
+}
+
+fn load_pad(name: &str) -> Pad {
+    match name {
+        "numpad" => Pad::from_layout(NUMPAD_LAYOUT),
+        "keypad" => Pad::from_layout(KEYPAD_LAYOUT),
+        path => Pad::from_layout(
+            &std::fs::read_to_string(path).unwrap_or_else(|e| panic!("can't read {path}: {e}")),
+        ),
+    }
+}
+
 fn main() {
     let input = parse(include_str!("../input.txt"));
-    println!("{}", part12(&input, 2));
-    println!("{}", part12(&input, 25));
+    let args: Vec<String> = std::env::args().collect();
+    let numpad = Pad::from_layout(NUMPAD_LAYOUT);
+    let keypad = Pad::from_layout(KEYPAD_LAYOUT);
+    let chains: Vec<Vec<Pad>> = match args.iter().position(|a| a == "--chain") {
+        // the first pad types the code, each following pad drives a robot at the previous one
+        Some(i) => vec![args[i + 1].split(',').map(load_pad).collect()],
+        None => args
+            .iter()
+            .position(|a| a == "--robots")
+            .map_or(vec![2, 25], |i| vec![args[i + 1].parse().unwrap()])
+            .into_iter()
+            .map(|keypad_robot_count| {
+                iter::once(numpad.clone())
+                    .chain(iter::repeat_n(keypad.clone(), keypad_robot_count))
+                    .collect()
+            })
+            .collect(),
+    };
+    if let Some(idx) = chains[0].iter().skip(1).position(|p| !p.can_control()) {
+        panic!("pad {} needs ^, v, <, > and A keys", idx + 2);
+    }
+
+    if let Some(i) = args.iter().position(|a| a == "--simulate") {
+        match simulate(&args[i + 1], &chains[0]) {
+            Ok(code) => println!("{code}"),
+            Err(e) => println!("{e}"),
+        }
+        return;
+    }
+    if args.iter().any(|a| a == "--sequence") {
+        let mut cache = HashMap::new();
+        let mut length_cache = HashMap::new();
+        for code in &input {
+            let Some(sequence) = min_sequence(code, &chains[0], &mut cache) else {
+                println!("{code}: can't be typed");
+                continue;
+            };
+            assert_eq!(simulate(&sequence, &chains[0]).as_ref(), Ok(code));
+            assert_eq!(
+                Some(sequence.len()),
+                min_length(code, &chains[0], &mut length_cache)
+            );
+            println!("{code}: {sequence}");
+        }
+    }
+    for pads in &chains {
+        let complexity = part12_table(&input, pads);
+        if args.iter().any(|a| a == "--check") {
+            assert_eq!(
+                complexity.as_ref().ok(),
+                part12(&input, pads)
+                    .map(|c| BigCount::from(c as u128))
+                    .as_ref()
+            );
+        }
+        match complexity {
+            Ok(complexity) => println!("{complexity}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+    }
 }
@@ -1,76 +1,428 @@
+use std::{cmp::Reverse, str::FromStr};
+
 use ahash::{AHashMap, AHashSet};
+use rayon::prelude::*;
+
+#[derive(Debug, Clone, Copy)]
+struct Params {
+    // mix in num << shifts[0], num >> shifts[1] and num << shifts[2], pruning after each
+    shifts: [u32; 3],
+    modulus: usize,
+    iterations: usize,
+    window: usize,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            shifts: [6, 5, 11],
+            modulus: 1 << 24,
+            iterations: 2000,
+            window: 4,
+        }
+    }
+}
+
+impl Params {
+    // price changes are in -9..=9
+    fn sequence_count(&self) -> usize {
+        19usize.pow(self.window as u32)
+    }
+
+    fn encode(&self, changes: &[i8]) -> Option<usize> {
+        (changes.len() == self.window && changes.iter().all(|c| (-9..=9).contains(c)))
+            .then(|| changes.iter().fold(0, |acc, c| acc * 19 + (c + 9) as usize))
+    }
+
+    fn decode(&self, sequence: usize) -> Vec<i8> {
+        (0..self.window as u32)
+            .rev()
+            .map(|w| (sequence / 19usize.pow(w) % 19) as i8 - 9)
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct Sale {
+    buyer: usize,
+    iteration: usize,
+    price: usize,
+}
 
 fn parse(input: &str) -> Vec<usize> {
     input.lines().map(|l| l.parse().unwrap()).collect()
 }
 
-fn evolve_number(num: &mut usize) {
-    *num ^= *num << 6;
-    *num &= 0xffffff;
-    *num ^= *num >> 5;
-    *num &= 0xffffff;
-    *num ^= *num << 11;
-    *num &= 0xffffff;
+fn evolve_number(num: &mut usize, params: &Params) {
+    *num ^= *num << params.shifts[0];
+    *num %= params.modulus;
+    *num ^= *num >> params.shifts[1];
+    *num %= params.modulus;
+    *num ^= *num << params.shifts[2];
+    *num %= params.modulus;
+}
+
+// returns the first two seeds found evolving into the same number, if any
+fn find_collision(params: &Params) -> Option<(usize, usize)> {
+    let evolved = |seed: usize| {
+        let mut num = seed;
+        evolve_number(&mut num, params);
+        num
+    };
+    let mut seen = vec![0u64; params.modulus.div_ceil(64)];
+    for seed in 0..params.modulus {
+        let num = evolved(seed);
+        if seen[num / 64] >> (num % 64) & 1 == 1 {
+            let first = (0..seed).find(|s| evolved(*s) == num).unwrap();
+            return Some((first, seed));
+        }
+        seen[num / 64] |= 1 << (num % 64);
+    }
+    None
+}
+
+// the evolution is linear over GF(2) when the modulus is a power of two,
+// column j holds the image of bit j
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BitMatrix {
+    cols: Vec<u64>,
+}
+
+impl BitMatrix {
+    fn identity(bits: usize) -> Self {
+        BitMatrix {
+            cols: (0..bits).map(|j| 1 << j).collect(),
+        }
+    }
+
+    fn step(params: &Params) -> Option<Self> {
+        if !params.modulus.is_power_of_two() || params.modulus > 1 << 63 {
+            return None;
+        }
+        let bits = params.modulus.trailing_zeros() as usize;
+        let cols = (0..bits)
+            .map(|j| {
+                let mut num = 1 << j;
+                evolve_number(&mut num, params);
+                num as u64
+            })
+            .collect();
+        Some(BitMatrix { cols })
+    }
+
+    fn apply(&self, v: u64) -> u64 {
+        self.cols
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| v >> j & 1 == 1)
+            .fold(0, |acc, (_, col)| acc ^ col)
+    }
+
+    // self after other
+    fn compose(&self, other: &BitMatrix) -> Self {
+        BitMatrix {
+            cols: other.cols.iter().map(|c| self.apply(*c)).collect(),
+        }
+    }
+
+    fn pow(&self, mut n: u64) -> Self {
+        let mut res = BitMatrix::identity(self.cols.len());
+        let mut base = self.clone();
+        while n > 0 {
+            if n & 1 == 1 {
+                res = res.compose(&base);
+            }
+            base = base.compose(&base);
+            n >>= 1;
+        }
+        res
+    }
+
+    // gauss-jordan on the columns, None if the matrix is singular
+    fn inverse(&self) -> Option<Self> {
+        let bits = self.cols.len();
+        let mut cols = self.cols.clone();
+        let mut inv = BitMatrix::identity(bits).cols;
+        for row in 0..bits {
+            let pivot = (row..bits).find(|j| cols[*j] >> row & 1 == 1)?;
+            cols.swap(row, pivot);
+            inv.swap(row, pivot);
+            for j in 0..bits {
+                if j != row && cols[j] >> row & 1 == 1 {
+                    cols[j] ^= cols[row];
+                    inv[j] ^= inv[row];
+                }
+            }
+        }
+        Some(BitMatrix { cols: inv })
+    }
+}
+
+// baby-step giant-step on the orbit of seed, None if seed never comes back
+fn cycle_length(step: &BitMatrix, seed: u64) -> Option<u64> {
+    let inverse = step.inverse()?;
+    let space = 1u64 << step.cols.len();
+    let m = (space as f64).sqrt().ceil() as u64;
+    let mut baby_steps = AHashMap::new();
+    let mut num = seed;
+    for j in 0..m {
+        if j > 0 && num == seed {
+            return Some(j);
+        }
+        baby_steps.insert(num, j);
+        num = step.apply(num);
+    }
+    let giant = inverse.pow(m);
+    let mut num = seed;
+    for i in 1..=space.div_ceil(m) {
+        num = giant.apply(num);
+        if let Some(j) = baby_steps.get(&num) {
+            return Some(i * m + j);
+        }
+    }
+    None
 }
 
-fn part1(initial_numbers: &[usize]) -> usize {
+fn part1(initial_numbers: &[usize], params: &Params) -> usize {
     initial_numbers
         .iter()
         .map(|num| {
             let mut res = *num;
-            (0..2000).for_each(|_| evolve_number(&mut res));
+            (0..params.iterations).for_each(|_| evolve_number(&mut res, params));
             res
         })
         .sum()
 }
 
-fn part2(numbers: &[usize]) -> isize {
-    let mut sequence_bananas: AHashMap<(i8, i8, i8, i8), Vec<i8>> = AHashMap::new();
+fn part1_jump(initial_numbers: &[usize], step: &BitMatrix, n: u64) -> u64 {
+    let jump = step.pow(n);
+    initial_numbers
+        .iter()
+        .map(|num| jump.apply(*num as u64))
+        .sum()
+}
+
+fn sequence_totals(numbers: &[usize], params: &Params) -> Vec<u32> {
+    let sequence_count = params.sequence_count();
+    let mut totals = vec![0; sequence_count];
+    // index of the last buyer that sold at a sequence, offset by one
+    let mut last_seen = vec![0; sequence_count];
+    for (buyer_idx, number) in numbers.iter().enumerate() {
+        let stamp = buyer_idx + 1;
+        let mut curr_magic = *number;
+        let mut prev_cost = curr_magic % 10;
+        let mut sequence = 0;
+        for i in 1..params.iterations {
+            evolve_number(&mut curr_magic, params);
+            let cost = curr_magic % 10;
+            sequence = (sequence * 19 + cost + 9 - prev_cost) % sequence_count;
+            prev_cost = cost;
+            if i >= params.window && last_seen[sequence] != stamp {
+                last_seen[sequence] = stamp;
+                totals[sequence] += cost as u32;
+            }
+        }
+    }
+    totals
+}
+
+// the top sequences with their totals, best first
+fn part2(numbers: &[usize], params: &Params, top: usize) -> Vec<(Vec<i8>, isize)> {
+    let chunk_size = numbers.len().div_ceil(rayon::current_num_threads()).max(1);
+    let totals = numbers
+        .par_chunks(chunk_size)
+        .map(|chunk| sequence_totals(chunk, params))
+        .reduce(
+            || vec![0; params.sequence_count()],
+            |mut a, b| {
+                a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                a
+            },
+        );
+    let mut sequences: Vec<usize> = (0..totals.len()).collect();
+    sequences.sort_unstable_by_key(|s| (Reverse(totals[*s]), *s));
+    sequences
+        .into_iter()
+        .take(top)
+        .map(|s| (params.decode(s), totals[s] as isize))
+        .collect()
+}
+
+// the first sale of every buyer that sees the sequence
+fn sales(numbers: &[usize], params: &Params, changes: &[i8]) -> Vec<Sale> {
+    let target = params
+        .encode(changes)
+        .unwrap_or_else(|| panic!("a sequence needs {} changes in -9..=9", params.window));
+    let sequence_count = params.sequence_count();
+    numbers
+        .iter()
+        .enumerate()
+        .filter_map(|(buyer, number)| {
+            let mut curr_magic = *number;
+            let mut prev_cost = curr_magic % 10;
+            let mut sequence = 0;
+            (1..params.iterations).find_map(|iteration| {
+                evolve_number(&mut curr_magic, params);
+                let price = curr_magic % 10;
+                sequence = (sequence * 19 + price + 9 - prev_cost) % sequence_count;
+                prev_cost = price;
+                (iteration >= params.window && sequence == target).then_some(Sale {
+                    buyer,
+                    iteration,
+                    price,
+                })
+            })
+        })
+        .collect()
+}
+
+fn part2_hashmap(numbers: &[usize], params: &Params) -> isize {
+    let mut sequence_bananas: AHashMap<Vec<i8>, Vec<i8>> = AHashMap::new();
     for number in numbers {
         let mut costs_and_changes: Vec<(i8, i8)> = vec![];
         let mut curr_magic = *number;
-        for _ in 0..2000 {
+        for _ in 0..params.iterations {
             let cost = (curr_magic % 10) as i8;
             costs_and_changes.push((cost, costs_and_changes.last().map_or(0, |l| cost - l.0)));
-            evolve_number(&mut curr_magic);
+            evolve_number(&mut curr_magic, params);
         }
         let sequence_costs = costs_and_changes
-            .windows(4)
+            .windows(params.window)
             .skip(1)
-            .map(|w| ((w[0].1, w[1].1, w[2].1, w[3].1), w[3].0));
+            .map(|w| (w.iter().map(|c| c.1).collect::<Vec<_>>(), w[w.len() - 1].0));
         let mut seen_sequences = AHashSet::new();
         for (k, v) in sequence_costs {
             if seen_sequences.contains(&k) {
                 continue;
             }
+            seen_sequences.insert(k.clone());
             sequence_bananas
                 .entry(k)
                 .and_modify(|v2| v2.push(v))
                 .or_insert(vec![v]);
-            seen_sequences.insert(k);
         }
     }
     sequence_bananas
-        .get(
-            sequence_bananas
-                .iter()
-                .max_by(|a, b| {
-                    a.1.iter()
-                        .map(|i| *i as isize)
-                        .sum::<isize>()
-                        .cmp(&b.1.iter().map(|i| *i as isize).sum::<isize>())
-                })
-                .unwrap()
-                .0,
-        )
-        .unwrap()
+        .values()
+        .map(|v| v.iter().map(|i| *i as isize).sum())
+        .max()
+        .unwrap_or(0)
+}
+
+fn format_changes(changes: &[i8]) -> String {
+    changes
         .iter()
-        .map(|i| *i as isize)
-        .sum()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn arg<T: FromStr>(args: &[String], name: &str) -> Option<T> {
+    args.iter().position(|a| a == name).map(|i| {
+        args[i + 1]
+            .parse()
+            .unwrap_or_else(|_| panic!("invalid value for {name}"))
+    })
 }
 
 fn main() {
     let input = parse(include_str!("../input.txt"));
-    println!("{}", part1(&input));
-    println!("{}", part2(&input));
+    let args: Vec<String> = std::env::args().collect();
+    let defaults = Params::default();
+    let params = Params {
+        shifts: args
+            .iter()
+            .position(|a| a == "--shifts")
+            .map_or(defaults.shifts, |i| {
+                args[i + 1]
+                    .split(',')
+                    .map(|s| s.parse().expect("invalid value for --shifts"))
+                    .collect::<Vec<_>>()
+                    .try_into()
+                    .expect("--shifts takes three comma separated values")
+            }),
+        modulus: arg(&args, "--modulus").unwrap_or(defaults.modulus),
+        iterations: arg(&args, "--iterations").unwrap_or(defaults.iterations),
+        window: arg(&args, "--window").unwrap_or(defaults.window),
+    };
+    if params.window == 0 || params.shifts.iter().any(|s| *s >= usize::BITS) {
+        eprintln!("window must be positive and shifts below {}", usize::BITS);
+        std::process::exit(1);
+    }
+    if args.iter().any(|a| a == "--validate") {
+        match find_collision(&params) {
+            Some((a, b)) => {
+                println!("not a bijection: {a} and {b} both evolve into the same number");
+                std::process::exit(1);
+            }
+            None => println!("generator is a bijection on 0..{}", params.modulus),
+        }
+    }
+    let step = BitMatrix::step(&params);
+    if let Some(n) = arg(&args, "--jump") {
+        let step = step.as_ref().expect("--jump needs a power of two modulus");
+        println!("{}", part1_jump(&input, step, n));
+    }
+    if args.iter().any(|a| a == "--previous") {
+        let step = step
+            .as_ref()
+            .expect("--previous needs a power of two modulus");
+        let inverse = step.inverse().expect("generator is not invertible");
+        for num in &input {
+            let previous = inverse.apply(*num as u64);
+            assert_eq!(step.apply(previous), *num as u64);
+            println!("{num} <- {previous}");
+        }
+    }
+    if let Some(seed) = arg(&args, "--cycle") {
+        let step = step.as_ref().expect("--cycle needs a power of two modulus");
+        match cycle_length(step, seed) {
+            Some(length) => println!("{seed} returns after {length} steps"),
+            None => println!("{seed} never returns"),
+        }
+    }
+    let sum = part1(&input, &params);
+    if let Some(step) = step
+        .as_ref()
+        .filter(|_| args.iter().any(|a| a == "--check"))
+    {
+        assert_eq!(
+            sum as u64,
+            part1_jump(&input, step, params.iterations as u64)
+        );
+    }
+    println!("{sum}");
+    let top = part2(&input, &params, arg(&args, "--top").unwrap_or(1).max(1));
+    let (best, bananas) = &top[0];
+    if args.iter().any(|a| a == "--check") {
+        assert_eq!(*bananas, part2_hashmap(&input, &params));
+    }
+    if args.iter().any(|a| a == "--top") {
+        for (changes, total) in &top {
+            println!("{}: {total}", format_changes(changes));
+        }
+    }
+    if let Some(i) = args.iter().position(|a| a == "--sales") {
+        let changes = match args[i + 1].as_str() {
+            "best" => best.clone(),
+            list => list
+                .split(',')
+                .map(|c| c.parse().expect("invalid value for --sales"))
+                .collect(),
+        };
+        let sales = sales(&input, &params, &changes);
+        for sale in &sales {
+            println!(
+                "buyer {} ({}) sells at iteration {} for {}",
+                sale.buyer, input[sale.buyer], sale.iteration, sale.price
+            );
+        }
+        println!(
+            "{} of {} buyers sell for {} bananas",
+            sales.len(),
+            input.len(),
+            sales.iter().map(|s| s.price).sum::<usize>()
+        );
+    }
+    println!("{bananas}");
 }
@@ -1,8 +1,6 @@
 use std::collections::{HashMap, HashSet};
 
 use itertools::Itertools;
-use petgraph::algo::maximal_cliques;
-use petgraph::prelude::*;
 
 fn parse(input: &str) -> Vec<(String, String)> {
     input
@@ -12,60 +10,280 @@ fn parse(input: &str) -> Vec<(String, String)> {
         .collect()
 }
 
-fn build_graph(edges: Vec<(String, String)>) -> UnGraph<String, ()> {
-    let mut graph = UnGraph::new_undirected();
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bitset {
+    words: Vec<u64>,
+}
+
+impl Bitset {
+    fn new(len: usize) -> Self {
+        Bitset {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    fn insert(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    fn remove(&mut self, i: usize) {
+        self.words[i / 64] &= !(1 << (i % 64));
+    }
+
+    fn and(&self, other: &Bitset) -> Bitset {
+        Bitset {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(a, b)| a & b)
+                .collect(),
+        }
+    }
+
+    fn and_not(&self, other: &Bitset) -> Bitset {
+        Bitset {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(a, b)| a & !b)
+                .collect(),
+        }
+    }
+
+    fn or(&self, other: &Bitset) -> Bitset {
+        Bitset {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(a, b)| a | b)
+                .collect(),
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, w)| {
+            let mut w = *w;
+            std::iter::from_fn(move || {
+                (w != 0).then(|| {
+                    let bit = w.trailing_zeros() as usize;
+                    w &= w - 1;
+                    i * 64 + bit
+                })
+            })
+        })
+    }
+}
+
+struct Lan {
+    names: Vec<String>,
+    adjacency: Vec<Bitset>,
+}
+
+fn build_graph(edges: Vec<(String, String)>) -> Lan {
+    let mut names = vec![];
     let mut node_indices = HashMap::new();
+    let edges = edges
+        .into_iter()
+        .map(|(a, b)| {
+            [a, b].map(|name| {
+                *node_indices.entry(name.clone()).or_insert_with(|| {
+                    names.push(name);
+                    names.len() - 1
+                })
+            })
+        })
+        .collect_vec();
 
-    for (a, b) in edges {
-        let from_index = *node_indices
-            .entry(a.clone())
-            .or_insert_with(|| graph.add_node(a));
-        let to_index = *node_indices
-            .entry(b.clone())
-            .or_insert_with(|| graph.add_node(b));
-        graph.add_edge(from_index, to_index, ());
+    let mut adjacency = vec![Bitset::new(names.len()); names.len()];
+    for [a, b] in edges {
+        adjacency[a].insert(b);
+        adjacency[b].insert(a);
     }
+    Lan { names, adjacency }
+}
 
-    graph
+fn part1(lan: &Lan) -> usize {
+    let mut count = 0;
+    for u in 0..lan.names.len() {
+        for v in lan.adjacency[u].iter().filter(|v| *v > u) {
+            count += lan.adjacency[u]
+                .and(&lan.adjacency[v])
+                .iter()
+                .filter(|w| *w > v)
+                .filter(|w| [u, v, *w].iter().any(|n| lan.names[*n].starts_with("t")))
+                .count();
+        }
+    }
+    count
 }
 
-fn part1(graph: &UnGraph<String, ()>) -> usize {
-    let mut cliques3 = HashSet::new();
-    for edge in graph.edge_references() {
-        for &w in graph
-            .neighbors(edge.source())
-            .collect::<HashSet<_>>()
-            .intersection(&graph.neighbors(edge.target()).collect::<HashSet<_>>())
-        {
-            let mut clique3 = [edge.source(), edge.target(), w];
-            clique3.sort_unstable();
-            cliques3.insert(clique3);
+// repeatedly removes a node of minimum remaining degree
+fn degeneracy_order(lan: &Lan) -> Vec<usize> {
+    let mut degrees = lan.adjacency.iter().map(Bitset::count).collect_vec();
+    let mut removed = vec![false; lan.names.len()];
+    let mut order = Vec::with_capacity(lan.names.len());
+    while let Some(v) = (0..lan.names.len())
+        .filter(|v| !removed[*v])
+        .min_by_key(|v| degrees[*v])
+    {
+        removed[v] = true;
+        order.push(v);
+        for u in lan.adjacency[v].iter() {
+            degrees[u] -= 1;
         }
     }
-    cliques3
+    order
+}
+
+fn bron_kerbosch(
+    lan: &Lan,
+    clique: &mut Vec<usize>,
+    mut candidates: Bitset,
+    mut excluded: Bitset,
+    report: &mut impl FnMut(&[usize]),
+) {
+    if candidates.is_empty() {
+        if excluded.is_empty() {
+            report(clique);
+        }
+        return;
+    }
+    // branching on the pivot's neighbours can't find anything new
+    let pivot = candidates
+        .or(&excluded)
         .iter()
-        .filter(|clique| {
-            clique
-                .iter()
-                .any(|n_idx| graph.node_weight(*n_idx).unwrap().starts_with("t"))
-        })
-        .count()
+        .max_by_key(|u| candidates.and(&lan.adjacency[*u]).count())
+        .unwrap();
+    for v in candidates
+        .and_not(&lan.adjacency[pivot])
+        .iter()
+        .collect_vec()
+    {
+        clique.push(v);
+        bron_kerbosch(
+            lan,
+            clique,
+            candidates.and(&lan.adjacency[v]),
+            excluded.and(&lan.adjacency[v]),
+            report,
+        );
+        clique.pop();
+        candidates.remove(v);
+        excluded.insert(v);
+    }
 }
 
-fn part2(graph: &Graph<String, (), Undirected>) -> String {
-    maximal_cliques(graph)
+fn maximal_cliques(lan: &Lan) -> Vec<Vec<usize>> {
+    let mut cliques = vec![];
+    let mut later = Bitset::new(lan.names.len());
+    (0..lan.names.len()).for_each(|v| later.insert(v));
+    let mut earlier = Bitset::new(lan.names.len());
+    for v in degeneracy_order(lan) {
+        later.remove(v);
+        bron_kerbosch(
+            lan,
+            &mut vec![v],
+            later.and(&lan.adjacency[v]),
+            earlier.and(&lan.adjacency[v]),
+            &mut |clique| cliques.push(clique.to_vec()),
+        );
+        earlier.insert(v);
+    }
+    cliques
+}
+
+// plain Bron–Kerbosch without pivoting or ordering, only used to cross-check
+fn maximal_cliques_naive(lan: &Lan) -> Vec<Vec<usize>> {
+    fn extend(
+        neighbours: &[HashSet<usize>],
+        clique: &mut Vec<usize>,
+        mut candidates: HashSet<usize>,
+        mut excluded: HashSet<usize>,
+        cliques: &mut Vec<Vec<usize>>,
+    ) {
+        if candidates.is_empty() && excluded.is_empty() {
+            cliques.push(clique.clone());
+        }
+        for v in candidates.clone() {
+            clique.push(v);
+            extend(
+                neighbours,
+                clique,
+                &candidates & &neighbours[v],
+                &excluded & &neighbours[v],
+                cliques,
+            );
+            clique.pop();
+            candidates.remove(&v);
+            excluded.insert(v);
+        }
+    }
+    let neighbours = lan
+        .adjacency
         .iter()
-        .max_by_key(|x| x.len())
-        .unwrap()
+        .map(|a| a.iter().collect::<HashSet<_>>())
+        .collect_vec();
+    let mut cliques = vec![];
+    extend(
+        &neighbours,
+        &mut vec![],
+        (0..lan.names.len()).collect(),
+        HashSet::new(),
+        &mut cliques,
+    );
+    cliques
+}
+
+fn password(lan: &Lan, clique: &[usize]) -> String {
+    clique
         .iter()
-        .map(|n_idx| graph.node_weight(*n_idx).unwrap().to_string())
+        .map(|n| lan.names[*n].as_str())
         .sorted_unstable()
         .join(",")
 }
 
+fn part2(lan: &Lan) -> String {
+    password(
+        lan,
+        maximal_cliques(lan).iter().max_by_key(|c| c.len()).unwrap(),
+    )
+}
+
 fn main() {
     let input = parse(include_str!("../input.txt"));
-    let graph = build_graph(input);
-    println!("{}", part1(&graph));
-    println!("{}", part2(&graph));
+    let lan = build_graph(input);
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|a| a == "--check") {
+        let cliques: HashSet<String> = maximal_cliques(&lan)
+            .iter()
+            .map(|c| password(&lan, c))
+            .collect();
+        let expected: HashSet<String> = maximal_cliques_naive(&lan)
+            .iter()
+            .map(|c| password(&lan, c))
+            .collect();
+        assert_eq!(cliques, expected);
+    }
+    if args.iter().any(|a| a == "--all") {
+        for clique in maximal_cliques(&lan)
+            .iter()
+            .map(|c| password(&lan, c))
+            .sorted_unstable()
+        {
+            println!("{clique}");
+        }
+    }
+    println!("{}", part1(&lan));
+    println!("{}", part2(&lan));
 }